@@ -2,26 +2,140 @@
 // Exceptions. See /LICENSE for license information.
 // SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
 
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{bail, Context, Result};
 use code_gen_utils::format_cc_ident;
+use itertools::Itertools;
 use proc_macro2::TokenStream;
 use quote::quote;
-use rustc_hir::{Item, ItemKind, Node, Unsafety};
+use rustc_hir::{BodyId, Constness, Item, ItemKind, Node, PatKind, Unsafety};
 use rustc_interface::Queries;
 use rustc_middle::dep_graph::DepContext;
-use rustc_middle::middle::exported_symbols::ExportedSymbol;
 use rustc_middle::ty::{self, Ty, TyCtxt}; // See <internal link>/ty.html#import-conventions
 use rustc_span::def_id::{LocalDefId, LOCAL_CRATE};
 use rustc_span::symbol::Ident;
 use rustc_target::spec::abi::Abi;
 use rustc_target::spec::PanicStrategy;
+use rustc_trait_selection::traits::{Obligation, ObligationCause, ObligationCtxt};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub struct GeneratedBindings {
     pub h_body: TokenStream,
+
+    /// Rust code that `h_body` depends on: `#[no_mangle]` thunks for Rust
+    /// functions that aren't themselves `extern "C"` (and therefore can't be
+    /// called directly from C++), the drop/clone/emplacement/field-accessor
+    /// thunks and field-offset assertions of bound structs, and (when a thunk
+    /// uses it) the `OutRef` support module.  Empty if the crate has none of
+    /// these.
+    pub rs_body: TokenStream,
+}
+
+/// Caller-supplied settings that steer how bindings get generated, for cases where the
+/// right answer depends on the target toolchain/codebase rather than on the Rust source
+/// alone.
+#[derive(Clone, Debug, Default)]
+pub struct BindingsConfig {
+    /// Keyed by the generic item's name; each entry is one requested instantiation, given
+    /// as the Rust syntax for each of the item's type arguments, in declaration order.
+    ///
+    /// Unlike a C++ class template (see `check_instantiation_closure` on the
+    /// `rs_bindings_from_cc` side), there's no call-site worklist Crubit can scan to
+    /// discover which instantiations of a Rust generic function are actually needed -- so
+    /// instead the caller opts a generic function into bindings by naming the concrete
+    /// types to monomorphize it with.
+    instantiations: BTreeMap<String, Vec<Vec<String>>>,
+
+    /// How `i128`/`u128` should be represented in the generated C++; see `Int128Backend`.
+    int128_backend: Int128Backend,
+}
+
+impl BindingsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests bindings for `item_name` monomorphized with `type_args` (e.g.
+    /// `vec!["i32".to_string()]` to request `item_name::<i32>`).
+    pub fn add_instantiation(&mut self, item_name: impl Into<String>, type_args: Vec<String>) {
+        self.instantiations.entry(item_name.into()).or_default().push(type_args);
+    }
+
+    fn instantiations_for(&self, item_name: &str) -> &[Vec<String>] {
+        self.instantiations.get(item_name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Selects how `i128`/`u128` get represented in the generated C++ (see
+    /// `Int128Backend`). Defaults to `Int128Backend::Unsupported`.
+    pub fn set_int128_backend(&mut self, backend: Int128Backend) {
+        self.int128_backend = backend;
+    }
+}
+
+/// Selects how `format_ty` represents Rust's 128-bit integer types (`i128`/`u128`), since
+/// neither has a standard C++ equivalent and the best available option depends on the
+/// target toolchain/codebase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Int128Backend {
+    /// Neither of the below can be assumed to be available; `i128`/`u128` are rejected with
+    /// a diagnostic, same as before this backend selector existed.
+    #[default]
+    Unsupported,
+    /// Map to Clang/GCC's compiler-builtin `__int128`/`unsigned __int128`. Needs no
+    /// `#include` -- it's a language extension, not a library type.
+    ClangBuiltin,
+    /// Map to Abseil's `absl::int128`/`absl::uint128`, recording the `"absl/numeric/int128.h"`
+    /// `#include` it needs.
+    Abseil,
+}
+
+/// A piece of generated C++ code together with the `#include`s (e.g.
+/// `"cstdint"`, to be rendered as `#include <cstdint>`) it depends on.
+#[derive(Clone)]
+struct CcSnippet {
+    includes: BTreeSet<&'static str>,
+    tokens: TokenStream,
+}
+
+impl CcSnippet {
+    /// Returns a `CcSnippet` that doesn't require any `#include`s.
+    fn no_includes(tokens: TokenStream) -> Self {
+        Self { includes: BTreeSet::new(), tokens }
+    }
+
+    /// Returns a `CcSnippet` that requires a single `#include` (a standard
+    /// library header such as `"cstdint"`, or a library header such as
+    /// `"absl/numeric/int128.h"`).
+    fn with_include(tokens: TokenStream, include: &'static str) -> Self {
+        Self { includes: [include].into_iter().collect(), tokens }
+    }
+}
+
+impl From<TokenStream> for CcSnippet {
+    fn from(tokens: TokenStream) -> Self {
+        Self::no_includes(tokens)
+    }
+}
+
+/// The C++ and (if needed) Rust-side code generated for a single Rust item.
+#[derive(Clone)]
+struct ItemSnippet {
+    cc: CcSnippet,
+
+    /// Rust-side code that `cc` relies on: a `#[no_mangle]` thunk that `cc`
+    /// calls into if `cc`'s function isn't itself `extern "C"`, or for a
+    /// struct its drop/clone/emplacement/field-accessor thunks and
+    /// field-offset assertions.  Empty (`quote! {}`) if there are none.
+    rs_thunks: TokenStream,
 }
 
 impl GeneratedBindings {
     pub fn generate(tcx: TyCtxt) -> Result<Self> {
+        Self::generate_with_config(tcx, &BindingsConfig::new())
+    }
+
+    /// Like `generate`, but honoring `config`'s monomorphization instantiations and
+    /// backend-specific type mappings (e.g. for `i128`/`u128`).
+    pub fn generate_with_config(tcx: TyCtxt, config: &BindingsConfig) -> Result<Self> {
         match tcx.sess().panic_strategy() {
             PanicStrategy::Unwind => bail!("No support for panic=unwind strategy (b/254049425)"),
             PanicStrategy::Abort => (),
@@ -36,21 +150,38 @@ impl GeneratedBindings {
             quote! { __COMMENT__ #txt __NEWLINE__ }
         };
 
-        let h_body = {
-            let crate_content = format_crate(tcx).unwrap_or_else(|err| {
+        let ItemSnippet { cc: CcSnippet { includes, tokens: crate_content }, rs_thunks: rs_body } =
+            format_crate(tcx, config).unwrap_or_else(|err| {
                 let txt = format!("Failed to generate bindings for the crate: {}", err);
-                quote! { __COMMENT__ #txt }
+                ItemSnippet { cc: CcSnippet::no_includes(quote! { __COMMENT__ #txt }), rs_thunks: quote! {} }
+            });
+
+        // Only pull in the `OutRef` support module if some thunk actually ended up using it
+        // -- most crates have no `Clone`-implementing, `#[repr(C)]` structs at all.
+        let rs_body = if rs_body.to_string().contains("OutRef") {
+            let out_ref_support = format_out_ref_support();
+            quote! { #out_ref_support #rs_body }
+        } else {
+            rs_body
+        };
+
+        let h_body = {
+            let includes = includes.into_iter().map(|header| {
+                let header: TokenStream =
+                    header.parse().expect("`header` is expected to be a valid C++ identifier");
+                quote! { __HASH_TOKEN__ include <#header> __NEWLINE__ }
             });
             // TODO(b/251445877): Replace `#pragma once` with include guards.
             quote! {
                 #top_comment
                 __HASH_TOKEN__ pragma once __NEWLINE__
                 __NEWLINE__
+                #( #includes )*
                 #crate_content
             }
         };
 
-        Ok(Self { h_body })
+        Ok(Self { h_body, rs_body })
     }
 }
 
@@ -68,48 +199,102 @@ where
     Ok(query_context.peek_mut().enter(f))
 }
 
-fn format_ty(ty: Ty) -> Result<TokenStream> {
+fn format_ty<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>, config: &BindingsConfig) -> Result<CcSnippet> {
     Ok(match ty.kind() {
         ty::TyKind::Tuple(types) => {
             if types.len() == 0 {
-                quote! { void }
+                CcSnippet::no_includes(quote! { void })
             } else {
                 // TODO(b/254097223): Add support for tuples.
                 bail!("Tuples are not supported yet: {} (b/254097223)", ty);
             }
         }
-        ty::TyKind::Bool => quote! { bool },
-        ty::TyKind::Float(ty::FloatTy::F32) => quote! { float },
-        ty::TyKind::Float(ty::FloatTy::F64) => quote! { double },
-
-        ty::TyKind::Char
-        | ty::TyKind::Int(
-            ty::IntTy::Isize | ty::IntTy::I8 | ty::IntTy::I16 | ty::IntTy::I32 | ty::IntTy::I64,
-        )
-        | ty::TyKind::Uint(
-            ty::UintTy::Usize
-            | ty::UintTy::U8
-            | ty::UintTy::U16
-            | ty::UintTy::U32
-            | ty::UintTy::U64,
-        ) => {
-            // TODO(b/254094545): Add support for returning TokenStream *and* include paths.
-            bail!("No support yet for `#include`ing C++ equivalent of `{ty}` (b/254094545)")
+        ty::TyKind::Bool => CcSnippet::no_includes(quote! { bool }),
+        ty::TyKind::Float(ty::FloatTy::F32) => CcSnippet::no_includes(quote! { float }),
+        ty::TyKind::Float(ty::FloatTy::F64) => CcSnippet::no_includes(quote! { double }),
+        // `char32_t` is a standalone builtin type (not a typedef) since C++11 -- no `#include`
+        // needed.
+        ty::TyKind::Char => CcSnippet::no_includes(quote! { char32_t }),
+
+        ty::TyKind::Int(ty::IntTy::Isize) => {
+            CcSnippet::with_include(quote! { std::intptr_t }, "cstdint")
+        }
+        ty::TyKind::Int(ty::IntTy::I8) => {
+            CcSnippet::with_include(quote! { std::int8_t }, "cstdint")
+        }
+        ty::TyKind::Int(ty::IntTy::I16) => {
+            CcSnippet::with_include(quote! { std::int16_t }, "cstdint")
+        }
+        ty::TyKind::Int(ty::IntTy::I32) => {
+            CcSnippet::with_include(quote! { std::int32_t }, "cstdint")
+        }
+        ty::TyKind::Int(ty::IntTy::I64) => {
+            CcSnippet::with_include(quote! { std::int64_t }, "cstdint")
+        }
+        ty::TyKind::Uint(ty::UintTy::Usize) => {
+            CcSnippet::with_include(quote! { std::uintptr_t }, "cstdint")
+        }
+        ty::TyKind::Uint(ty::UintTy::U8) => {
+            CcSnippet::with_include(quote! { std::uint8_t }, "cstdint")
+        }
+        ty::TyKind::Uint(ty::UintTy::U16) => {
+            CcSnippet::with_include(quote! { std::uint16_t }, "cstdint")
+        }
+        ty::TyKind::Uint(ty::UintTy::U32) => {
+            CcSnippet::with_include(quote! { std::uint32_t }, "cstdint")
+        }
+        ty::TyKind::Uint(ty::UintTy::U64) => {
+            CcSnippet::with_include(quote! { std::uint64_t }, "cstdint")
         }
 
-        ty::TyKind::Int(ty::IntTy::I128) | ty::TyKind::Uint(ty::UintTy::U128) => {
-            // TODO(b/254094650): Consider mapping this to Clang's (and GCC's) `__int128`
-            // or to `absl::in128`.
-            bail!("C++ doesn't have a standard equivalent of `{ty}` (b/254094650)");
+        ty::TyKind::Int(ty::IntTy::I128) => match config.int128_backend {
+            Int128Backend::Unsupported => {
+                bail!("C++ doesn't have a standard equivalent of `{ty}` (b/254094650)")
+            }
+            Int128Backend::ClangBuiltin => CcSnippet::no_includes(quote! { __int128 }),
+            Int128Backend::Abseil => {
+                CcSnippet::with_include(quote! { absl::int128 }, "absl/numeric/int128.h")
+            }
+        },
+        ty::TyKind::Uint(ty::UintTy::U128) => match config.int128_backend {
+            Int128Backend::Unsupported => {
+                bail!("C++ doesn't have a standard equivalent of `{ty}` (b/254094650)")
+            }
+            Int128Backend::ClangBuiltin => CcSnippet::no_includes(quote! { unsigned __int128 }),
+            Int128Backend::Abseil => {
+                CcSnippet::with_include(quote! { absl::uint128 }, "absl/numeric/int128.h")
+            }
+        },
+
+        ty::TyKind::RawPtr(ty::TypeAndMut { ty: pointee_ty, mutbl }) => {
+            format_ptr_or_ref_ty(tcx, *pointee_ty, *mutbl, /* is_ref= */ false, config)?
+        }
+        ty::TyKind::Ref(_region, pointee_ty, mutbl) => {
+            format_ptr_or_ref_ty(tcx, *pointee_ty, *mutbl, /* is_ref= */ true, config)?
+        }
+
+        ty::TyKind::Adt(adt_def, substs) => {
+            if !substs.is_empty() {
+                bail!("Generic ADTs are not supported yet: {ty}");
+            }
+            if !adt_def.is_struct() {
+                bail!("Only structs are supported so far, not enums/unions: {ty}");
+            }
+            if !adt_def.repr().c() && !adt_def.repr().transparent() {
+                bail!(
+                    "Only `#[repr(C)]` or `#[repr(transparent)]` structs can be used in a \
+                     public API, because Rust's default layout is unspecified: {ty}"
+                );
+            }
+            let cc_name = format_cc_ident(tcx.item_name(adt_def.did()).as_str())
+                .with_context(|| format!("Error formatting the name of `{ty}`"))?;
+            CcSnippet::no_includes(quote! { #cc_name })
         }
 
-        ty::TyKind::Adt(..)
-        | ty::TyKind::Foreign(..)
+        ty::TyKind::Foreign(..)
         | ty::TyKind::Str
         | ty::TyKind::Array(..)
         | ty::TyKind::Slice(..)
-        | ty::TyKind::RawPtr(..)
-        | ty::TyKind::Ref(..)
         | ty::TyKind::FnPtr(..)
         | ty::TyKind::Dynamic(..)
         | ty::TyKind::Generator(..)
@@ -141,22 +326,126 @@ fn format_ty(ty: Ty) -> Result<TokenStream> {
     })
 }
 
+/// Formats a `*const T`/`*mut T` raw pointer (when `is_ref` is `false`) or a `&T`/`&mut T`
+/// reference (when `is_ref` is `true`) as the corresponding C++ `const T*`/`T*` or
+/// `const T&`/`T&`.
+///
+/// Slices and `str` behind a pointer/reference are rejected here with a dedicated message,
+/// since (unlike a thin pointer/reference to a sized type) they need a fat-pointer ABI that
+/// Crubit doesn't support yet. Any other unsupported `pointee_ty` surfaces its own
+/// `format_ty` error unchanged.
+fn format_ptr_or_ref_ty<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    pointee_ty: Ty<'tcx>,
+    mutbl: ty::Mutability,
+    is_ref: bool,
+    config: &BindingsConfig,
+) -> Result<CcSnippet> {
+    if matches!(pointee_ty.kind(), ty::TyKind::Slice(..) | ty::TyKind::Str) {
+        let kind = if is_ref { "reference" } else { "pointer" };
+        bail!(
+            "Formatting a {kind} to slice/`str` is not supported yet: \
+             {pointee_ty} needs a fat-pointer ABI"
+        );
+    }
+
+    let CcSnippet { includes, tokens: pointee_cc_ty } = format_ty(tcx, pointee_ty, config)?;
+    let cc_pointee = match mutbl {
+        ty::Mutability::Not => quote! { const #pointee_cc_ty },
+        ty::Mutability::Mut => pointee_cc_ty,
+    };
+    Ok(CcSnippet {
+        includes,
+        tokens: if is_ref { quote! { #cc_pointee & } } else { quote! { #cc_pointee * } },
+    })
+}
+
 /// Formats a function with the given `def_id` and `fn_name`.
 ///
 /// Will panic if `def_id` is invalid or doesn't identify a function.
-fn format_fn(tcx: TyCtxt, def_id: LocalDefId, fn_name: &Ident) -> Result<TokenStream> {
-    let sig = tcx
-        .fn_sig(def_id.to_def_id())
-        .no_bound_vars()
-        .expect("Caller (e.g. `format_def`) should verify no unbound generic vars");
+fn format_fn(
+    tcx: TyCtxt,
+    def_id: LocalDefId,
+    fn_name: &Ident,
+    body_id: BodyId,
+    config: &BindingsConfig,
+) -> Result<ItemSnippet> {
+    let sig = liberate_fn_sig(tcx, def_id.to_def_id(), tcx.fn_sig(def_id.to_def_id()))?;
+    let rs_fn_name = format_rs_ident(fn_name.as_str());
+    format_fn_with_sig(tcx, def_id, fn_name.as_str(), quote! { #rs_fn_name }, body_id, sig, config)
+}
+
+/// Checks that `fn_sig`'s `Binder` only binds late-bound *regions* (type and const
+/// generics of a free fn are always early-bound, and so never show up as bound vars here
+/// -- but check anyway, since `liberate_late_bound_regions` below would be the wrong thing
+/// to reach for if that ever stopped being true), then erases those regions to obtain a
+/// concrete `FnSig`.
+///
+/// C++ has no lifetime system, so late-bound lifetimes -- e.g. the implicit `'a` that
+/// lifetime elision inserts in `fn foo(arg: &i32) -> &i32` -- carry no information we need
+/// to preserve; erasing them lets us keep formatting the function instead of rejecting it
+/// outright.
+fn liberate_fn_sig<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: rustc_span::def_id::DefId,
+    fn_sig: ty::PolyFnSig<'tcx>,
+) -> Result<ty::FnSig<'tcx>> {
+    if fn_sig.bound_vars().iter().any(|var| !matches!(var, ty::BoundVariableKind::Region(_))) {
+        bail!("Functions with late-bound type or const parameters are not supported yet");
+    }
+    Ok(tcx.liberate_late_bound_regions(def_id, fn_sig))
+}
 
+/// Does the actual work of formatting a (possibly monomorphized) function signature
+/// `sig` as a C++ declaration plus (if needed) a Rust thunk.
+///
+/// `mangled_name` is the name used for the C++-visible declaration and (if a thunk is
+/// needed) the thunk itself; for a non-generic function this is just `fn_name`, but a
+/// monomorphized instantiation of a generic function uses a name that also encodes its
+/// type arguments (e.g. `generic_function__i32`), since C++ has no notion of generics to
+/// overload on. `rs_callee` is the Rust expression the thunk calls into -- `fn_name` for a
+/// non-generic function, or `fn_name::<ConcreteType>` (a turbofish) for a monomorphized
+/// instantiation.
+fn format_fn_with_sig(
+    tcx: TyCtxt,
+    def_id: LocalDefId,
+    mangled_name: &str,
+    rs_callee: TokenStream,
+    body_id: BodyId,
+    sig: ty::FnSig,
+    config: &BindingsConfig,
+) -> Result<ItemSnippet> {
     if sig.c_variadic {
         // TODO(b/254097223): Add support for variadic functions.
         bail!("C variadic functions are not supported (b/254097223)");
     }
-    if sig.inputs().len() != 0 {
-        // TODO(lukasza): Add support for function parameters.
-        bail!("Function parameters are not supported yet");
+
+    let hir_params = tcx.hir().body(body_id).params;
+    let mut includes = BTreeSet::new();
+    let mut cc_params = Vec::with_capacity(sig.inputs().len());
+    let mut cc_arg_names = Vec::with_capacity(sig.inputs().len());
+    let mut rs_params = Vec::with_capacity(sig.inputs().len());
+    let mut rs_arg_names = Vec::with_capacity(sig.inputs().len());
+    for (i, (&param_ty, hir_param)) in sig.inputs().iter().zip(hir_params.iter()).enumerate() {
+        let CcSnippet { includes: param_includes, tokens: param_cc_ty } = format_ty(tcx, param_ty, config)
+            .with_context(|| format!("Error formatting the type of parameter {i}"))?;
+        includes.extend(param_includes);
+
+        // Fall back to a synthesized name (e.g. for parameters bound via a non-trivial
+        // pattern, like `(a, b): (i32, i32)`) rather than rejecting the whole function.
+        let param_name = match hir_param.pat.kind {
+            PatKind::Binding(_, _, ident, _) => ident.as_str().to_string(),
+            _ => format!("__param_{i}"),
+        };
+        let cc_param_name = format_cc_ident(&param_name)
+            .with_context(|| format!("Error formatting the name of parameter {i}"))?;
+        cc_params.push(quote! { #param_cc_ty #cc_param_name });
+        cc_arg_names.push(cc_param_name);
+
+        let rs_param_name = format_rs_ident(&param_name);
+        let rs_param_ty = format_rs_ty_tokens(param_ty);
+        rs_params.push(quote! { #rs_param_name: #rs_param_ty });
+        rs_arg_names.push(rs_param_name);
     }
 
     match sig.unsafety {
@@ -186,260 +475,1915 @@ fn format_fn(tcx: TyCtxt, def_id: LocalDefId, fn_name: &Ident) -> Result<TokenSt
         // in a "C" ABI.
         _ => true,
     };
-    if need_thunk {
-        // TODO(b/254097223): Add support for Rust thunks.
-        bail!(
-            "Functions that require Rust thunks (e.g. non-`extern \"C\"`) are not supported yet \
-               (b/254097223)"
-        );
+
+    let CcSnippet { includes: ret_includes, tokens: ret_cc_ty } =
+        format_ty(tcx, sig.output(), config).context("Error formatting function return type")?;
+    includes.extend(ret_includes);
+    let cc_fn_name = format_cc_ident(mangled_name).context("Error formatting function name")?;
+    let target_feature_attr = format_target_feature_attr(tcx, def_id)?;
+
+    if !need_thunk {
+        return Ok(ItemSnippet {
+            cc: CcSnippet {
+                includes,
+                tokens: quote! {
+                    extern "C" #target_feature_attr #ret_cc_ty #cc_fn_name ( #(#cc_params),* );
+                },
+            },
+            rs_thunks: quote! {},
+        });
+    }
+
+    // `mangled_name` doesn't use the "C" ABI, so it can't be called directly from C++.  Emit
+    // a `#[no_mangle] pub extern "C"` Rust thunk with a stable ABI that forwards to it, and
+    // have the original name in C++ resolve to a thin inline wrapper that calls the thunk.
+    let thunk_name = format!("__crubit_thunk_{}", mangled_name);
+    let cc_thunk_name =
+        format_cc_ident(&thunk_name).context("Error formatting the name of the Rust thunk")?;
+    let rs_thunk_name = format_rs_ident(&thunk_name);
+    // Rust (unlike C++) doesn't need an explicit `-> ()` for a unit-returning fn.
+    let rs_ret_sig = if is_unit_type(sig.output()) {
+        quote! {}
+    } else {
+        let rs_ret_ty = format_rs_ty_tokens(sig.output());
+        quote! { -> #rs_ret_ty }
+    };
+
+    let cc_call = quote! { #cc_thunk_name( #(#cc_arg_names),* ) };
+    let cc_wrapper_body =
+        if is_unit_type(sig.output()) { quote! { #cc_call; } } else { quote! { return #cc_call; } };
+
+    // `const fn`s forward to the thunk at runtime (the thunk itself isn't
+    // `constexpr`-evaluable by the C++ compiler), so mark the wrapper `constexpr`
+    // rather than `consteval` -- callers can still use it in constant expressions
+    // when the thunk happens to be foldable, and fall back to a normal call
+    // otherwise.
+    let cc_constexpr = match tcx.constness(def_id.to_def_id()) {
+        Constness::Const => quote! { constexpr },
+        Constness::NotConst => quote! {},
+    };
+
+    Ok(ItemSnippet {
+        cc: CcSnippet {
+            includes,
+            tokens: quote! {
+                extern "C" #ret_cc_ty #cc_thunk_name ( #(#cc_params),* );
+                inline #cc_constexpr #target_feature_attr #ret_cc_ty #cc_fn_name ( #(#cc_params),* ) {
+                    #cc_wrapper_body
+                }
+            },
+        },
+        rs_thunks: quote! {
+            #[no_mangle]
+            pub extern "C" fn #rs_thunk_name ( #(#rs_params),* ) #rs_ret_sig {
+                #rs_callee ( #(#rs_arg_names),* )
+            }
+        },
+    })
+}
+
+/// Formats the `__attribute__((target("...")))` needed for `def_id`'s C++
+/// declaration(s) to reflect any `#[target_feature(enable = "...")]` the Rust
+/// function was compiled with.  Returns an empty `TokenStream` if the
+/// function doesn't require any target features.
+fn format_target_feature_attr(tcx: TyCtxt, def_id: LocalDefId) -> Result<TokenStream> {
+    let rust_features = &tcx.codegen_fn_attrs(def_id.to_def_id()).target_features;
+    if rust_features.is_empty() {
+        return Ok(quote! {});
     }
 
-    let ret_type = format_ty(sig.output()).context("Error formatting function return type")?;
-    let fn_name = format_cc_ident(fn_name.as_str()).context("Error formatting function name")?;
+    let cc_features = rust_features
+        .iter()
+        .map(|feature| {
+            let rust_name = feature.as_str();
+            target_feature_cc_name(rust_name).with_context(|| {
+                format!(
+                    "Rust target feature `{rust_name}` has no known C++ equivalent (b/254096564)"
+                )
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let target_str = cc_features.join(",");
+    Ok(quote! { __attribute__((target(#target_str))) })
+}
 
-    Ok(quote! {
-        extern "C" #ret_type #fn_name ();
+/// Maps a Rust target feature name (as it appears in
+/// `#[target_feature(enable = "...")]`) to the name Clang/GCC expect in
+/// `__attribute__((target("...")))`.  Most names are spelled identically by
+/// rustc and by Clang/GCC; only the handful of exceptions below are known.
+fn target_feature_cc_name(rust_feature: &str) -> Option<&'static str> {
+    Some(match rust_feature {
+        "sse" => "sse",
+        "sse2" => "sse2",
+        "sse3" => "sse3",
+        "ssse3" => "ssse3",
+        "sse4.1" => "sse4.1",
+        "sse4.2" => "sse4.2",
+        "avx" => "avx",
+        "avx2" => "avx2",
+        "avx512f" => "avx512f",
+        "fma" => "fma",
+        "aes" => "aes",
+        "popcnt" => "popcnt",
+        "lzcnt" => "lzcnt",
+        "bmi1" => "bmi",
+        "bmi2" => "bmi2",
+        "pclmulqdq" => "pclmul",
+        "rdrand" => "rdrnd",
+        "rdseed" => "rdseed",
+        "neon" => "neon",
+        _ => return None,
     })
 }
 
+/// Returns `true` if `ty` is the unit type `()`.
+fn is_unit_type(ty: Ty) -> bool {
+    matches!(ty.kind(), ty::TyKind::Tuple(types) if types.len() == 0)
+}
+
+/// Formats `name` as a `proc_macro2::Ident` suitable for interpolating into a
+/// `quote!`-generated Rust thunk.
+fn format_rs_ident(name: &str) -> proc_macro2::Ident {
+    proc_macro2::Ident::new(name, proc_macro2::Span::call_site())
+}
+
+/// Formats `name` (a `rustc_middle::ty::FieldDef::name`, as seen by
+/// `collect_nested_field_bindings`) as a single field-access path segment: a named identifier
+/// for an ordinary struct field, or a bare unsuffixed integer literal for a tuple-struct
+/// field (whose `FieldDef::name` is its decimal index, e.g. `"0"`, `"1"`, ...).
+///
+/// `proc_macro2::Ident::new` (what `format_rs_ident` wraps) panics on a non-identifier string
+/// like `"0"`, so tuple fields can't go through it -- but both `offset_of!` and ordinary
+/// field-access expressions (`self_.0`) accept a bare integer literal in a field position just
+/// as readily as an identifier, so this returns the right token either way.
+fn format_rs_field_path_segment(name: &str) -> TokenStream {
+    match name.parse::<u32>() {
+        Ok(index) => {
+            let index = proc_macro2::Literal::u32_unsuffixed(index);
+            quote! { #index }
+        }
+        Err(_) => {
+            let ident = format_rs_ident(name);
+            quote! { #ident }
+        }
+    }
+}
+
+/// Formats `name` (a `rustc_middle::ty::FieldDef::name`) as the C++ member name of the
+/// mirrored struct's field, handling the tuple-struct case where `name` is a bare decimal
+/// index (`"0"`, `"1"`, ...) rather than a valid C++ identifier on its own.
+///
+/// Tuple-struct fields are prefixed with `_` (`_0`, `_1`, ...) so the mirrored C++ struct
+/// still gets a legal member name for them -- this only affects the C++-side spelling;
+/// `collect_nested_field_bindings` keeps addressing the real field on the Rust side by its
+/// numeric index via `format_rs_field_path_segment`, since that's what `offset_of!` and
+/// ordinary field access need there.
+fn format_cc_field_ident(name: &str) -> Result<proc_macro2::Ident> {
+    if name.parse::<u32>().is_ok() {
+        format_cc_ident(&format!("_{name}"))
+    } else {
+        format_cc_ident(name)
+    }
+}
+
+/// Formats the Rust spelling of `ty`, for use in a `quote!`-generated Rust
+/// thunk.
+///
+/// Only meant to be called with a `ty` that `format_ty` has already accepted
+/// (i.e. one of the small set of types supported today) -- for those types
+/// `Ty`'s `Display` impl happens to print valid Rust syntax (e.g. `i32`,
+/// `u32`, `bool`, `()`), so there's no need for a separate type-by-type match.
+fn format_rs_ty_tokens(ty: Ty) -> TokenStream {
+    ty.to_string()
+        .parse()
+        .expect("`Ty::to_string()` of a `format_ty`-supported type should be valid Rust syntax")
+}
+
 /// Formats a Rust item idenfied by `def_id`.
 ///
 /// Will panic if `def_id` is invalid (i.e. doesn't identify a Rust node or
 /// item).
-fn format_def(tcx: TyCtxt, def_id: LocalDefId) -> Result<TokenStream> {
+fn format_def(tcx: TyCtxt, def_id: LocalDefId, config: &BindingsConfig) -> Result<ItemSnippet> {
     match tcx.hir().get_by_def_id(def_id) {
         Node::Item(item) => match item {
-            Item { ident, kind: ItemKind::Fn(_hir_fn_sig, generics, _body), .. } => {
-                if generics.params.len() == 0 {
-                    format_fn(tcx, def_id, &ident)
+            Item { ident, kind: ItemKind::Fn(_hir_fn_sig, generics, body_id), .. } => {
+                // Lifetime params (including the ones lifetime elision spells out in HIR,
+                // e.g. for `fn foo(arg: &i32) -> &i32`) are fine -- `format_fn` erases them.
+                // Type and const params are the only generics we still reject here.
+                let has_type_or_const_param = generics
+                    .params
+                    .iter()
+                    .any(|param| !matches!(param.kind, rustc_hir::GenericParamKind::Lifetime { .. }));
+                if has_type_or_const_param {
+                    format_generic_fn_instantiations(tcx, def_id, &ident, *body_id, config)
                 } else {
-                    bail!(
-                        "Generic functions (lifetime-generic or type-generic) are not supported yet"
-                    )
+                    format_fn(tcx, def_id, &ident, *body_id, config)
                 }
             }
+            Item { ident, kind: ItemKind::Struct(_variant_data, generics), .. } => {
+                format_adt(tcx, def_id, &ident, generics, config)
+            }
             Item { kind, .. } => bail!("Unsupported rustc_hir::hir::ItemKind: {}", kind.descr()),
         },
         _unsupported_node => bail!("Unsupported rustc_hir::hir::Node"),
     }
 }
 
-/// Formats a C++ comment explaining why no bindings have been generated for
-/// `local_def_id`.
-fn format_unsupported_def(
+/// Formats a type/const-generic function by monomorphizing it once per instantiation that
+/// `config` requests for it (keyed by `fn_name`), rather than emitting a single generic
+/// binding (which C++ has no equivalent of).
+///
+/// Bails with the classic "not supported" diagnostic if `config` requests no instantiations
+/// at all for this function. Each individual requested instantiation that turns out to be
+/// unsatisfiable (wrong arity, unsatisfied trait bounds, or an unformattable type) is skipped
+/// with its own explanatory comment instead of failing the whole item, so that one bad
+/// instantiation doesn't hide bindings for the others.
+fn format_generic_fn_instantiations(
     tcx: TyCtxt,
-    local_def_id: LocalDefId,
-    err: anyhow::Error,
-) -> TokenStream {
-    let span = tcx.sess().source_map().span_to_embeddable_string(tcx.def_span(local_def_id));
-    let name = tcx.def_path_str(local_def_id.to_def_id());
+    def_id: LocalDefId,
+    fn_name: &Ident,
+    body_id: BodyId,
+    config: &BindingsConfig,
+) -> Result<ItemSnippet> {
+    let instantiations = config.instantiations_for(fn_name.as_str());
+    if instantiations.is_empty() {
+        bail!(
+            "Generic functions (lifetime-generic or type-generic) are not supported yet, \
+             unless the caller requests specific instantiations via `BindingsConfig`"
+        );
+    }
 
-    // https://docs.rs/anyhow/latest/anyhow/struct.Error.html#display-representations
-    // says: To print causes as well [...], use the alternate selector “{:#}”.
-    let msg = format!("Error generating bindings for `{name}` defined at {span}: {err:#}");
+    let mut includes = BTreeSet::new();
+    let mut cc_tokens = Vec::new();
+    let mut rs_thunks = Vec::new();
+    for type_args in instantiations {
+        let snippet = format_one_generic_fn_instantiation(tcx, def_id, fn_name, body_id, type_args, config)
+            .unwrap_or_else(|err| {
+                let mangled_args = type_args.join(", ");
+                ItemSnippet {
+                    cc: CcSnippet::no_includes(format_unsupported_instantiation(
+                        fn_name.as_str(),
+                        &mangled_args,
+                        err,
+                    )),
+                    rs_thunks: quote! {},
+                }
+            });
+        includes.extend(snippet.cc.includes);
+        cc_tokens.push(snippet.cc.tokens);
+        rs_thunks.push(snippet.rs_thunks);
+    }
+
+    Ok(ItemSnippet {
+        cc: CcSnippet { includes, tokens: quote! { #( #cc_tokens )* } },
+        rs_thunks: quote! { #( #rs_thunks )* },
+    })
+}
+
+/// Formats a C++ comment explaining why no bindings were generated for one requested
+/// instantiation (`fn_name::<type_args>`) of a generic function.
+fn format_unsupported_instantiation(fn_name: &str, type_args: &str, err: anyhow::Error) -> TokenStream {
+    let msg = format!(
+        "Error generating bindings for `{fn_name}::<{type_args}>`: {err:#}",
+        err = err
+    );
     quote! { __NEWLINE__ __NEWLINE__ __COMMENT__ #msg __NEWLINE__ }
 }
 
-/// Formats all public items from the Rust crate being compiled (aka the
-/// `LOCAL_CRATE`).
-fn format_crate(tcx: TyCtxt) -> Result<TokenStream> {
-    let crate_name = format_cc_ident(tcx.crate_name(LOCAL_CRATE).as_str())?;
+/// Monomorphizes `def_id` with `type_args` (Rust syntax for each type argument, in
+/// declaration order), checks that the substitution satisfies the function's trait bounds,
+/// and formats the resulting concrete function as `fn_name__type_arg1__type_arg2__...`.
+fn format_one_generic_fn_instantiation(
+    tcx: TyCtxt,
+    def_id: LocalDefId,
+    fn_name: &Ident,
+    body_id: BodyId,
+    type_args: &[String],
+    config: &BindingsConfig,
+) -> Result<ItemSnippet> {
+    let generics = tcx.generics_of(def_id.to_def_id());
+    if generics.own_counts().types != type_args.len() {
+        bail!(
+            "Expected {} type argument(s), found {}",
+            generics.own_counts().types,
+            type_args.len()
+        );
+    }
 
-    // TODO(lukasza): We probably shouldn't be using `exported_symbols` as the main
-    // entry point for finding Rust definitions that need to be wrapping in C++
-    // bindings.  For example, it _seems_ that things like `type` aliases or
-    // `struct`s (without an `impl`) won't be visible to a linker and therefore
-    // won't have exported symbols.  Additionally, walking Rust's modules top-down
-    // might result in easier translation into C++ namespaces.
-    let snippets =
-        tcx.exported_symbols(LOCAL_CRATE).iter().filter_map(move |(symbol, _)| match symbol {
-            ExportedSymbol::NonGeneric(def_id) => {
-                // It seems that non-generic exported symbols should all be defined in the
-                // `LOCAL_CRATE`.  Furthermore, `def_id` seems to be a `LocalDefId`.  OTOH, it
-                // isn't clear why `ExportedSymbol::NonGeneric` holds a `DefId` rather than a
-                // `LocalDefId`.  For now, we assert `expect_local` below (and if it fails, then
-                // hopefully it will help us understand these things better and maybe add
-                // extra unit tests against out code).
-                let local_id = def_id.expect_local();
-
-                Some(match format_def(tcx, local_id) {
-                    Ok(snippet) => snippet,
-                    Err(err) => format_unsupported_def(tcx, local_id, err),
-                })
-            }
-            ExportedSymbol::Generic(def_id, _substs) => {
-                // Ignore non-local defs.  Map local defs to an unsupported comment.
-                //
-                // We are guessing that a non-local `def_id` can happen when the `LOCAL_CRATE`
-                // exports a monomorphization/specialization of a generic defined in a different
-                // crate.  One specific example (covered via `async fn` in one of the tests) is
-                // `DefId(2:14250 ~ core[ef75]::future::from_generator)`.
-                def_id.as_local().map(|local_id| {
-                    format_unsupported_def(tcx, local_id, anyhow!("Generics are not supported yet."))
-                })
-            }
-            ExportedSymbol::DropGlue(..) | ExportedSymbol::NoDefId(..) => None,
-        });
+    let substs = parse_substs(tcx, def_id.to_def_id(), type_args)?;
+    ensure_substitution_satisfies_bounds(tcx, def_id.to_def_id(), substs)
+        .context("The requested instantiation doesn't satisfy the function's trait bounds")?;
 
-    Ok(quote! {
-        namespace #crate_name {
-            #( #snippets )*
-        }
-    })
-}
+    let sig = liberate_fn_sig(
+        tcx,
+        def_id.to_def_id(),
+        tcx.fn_sig(def_id.to_def_id()).subst(tcx, substs),
+    )?;
 
-#[cfg(test)]
-pub mod tests {
-    use super::{format_def, format_ty, GeneratedBindings};
+    let mangled_suffix =
+        type_args.iter().map(|arg| arg.chars().filter(|c| c.is_alphanumeric()).collect::<String>()).join("__");
+    let mangled_name = format!("{}__{}", fn_name.as_str(), mangled_suffix);
 
-    use anyhow::Result;
-    use itertools::Itertools;
-    use proc_macro2::TokenStream;
-    use quote::quote;
-    use rustc_middle::ty::{Ty, TyCtxt};
-    use rustc_span::def_id::LocalDefId;
-    use std::path::PathBuf;
+    let rs_fn_name = format_rs_ident(fn_name.as_str());
+    let rs_type_args: Vec<TokenStream> =
+        type_args.iter().map(|arg| arg.parse::<TokenStream>()).collect::<std::result::Result<_, _>>()
+            .map_err(|err| anyhow::anyhow!("Error parsing type argument tokens: {err}"))?;
+    let rs_callee = quote! { #rs_fn_name::<#(#rs_type_args),*> };
 
-    use token_stream_matchers::{assert_cc_matches, assert_cc_not_matches};
+    format_fn_with_sig(tcx, def_id, &mangled_name, rs_callee, body_id, sig, config)
+}
 
-    pub fn get_sysroot_for_testing() -> PathBuf {
-        let runfiles = runfiles::Runfiles::create().unwrap();
-        runfiles.rlocation(if std::env::var("LEGACY_TOOLCHAIN_RUST_TEST").is_ok() {
-            "google3/third_party/unsupported_toolchains/rust/toolchains/nightly"
-        } else {
-            "google3/nowhere/llvm/rust"
-        })
-    }
+/// Parses each of `type_args` (Rust syntax) into a `Ty` and builds the `substs` list that
+/// `def_id`'s generic parameters (in declaration order) would be instantiated with.
+fn parse_substs<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: rustc_span::def_id::DefId,
+    type_args: &[String],
+) -> Result<ty::SubstsRef<'tcx>> {
+    let tys = type_args
+        .iter()
+        .map(|arg| parse_type_arg(tcx, arg))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(tcx.mk_substs(tys.into_iter().map(ty::GenericArg::from)))
+}
 
-    #[test]
-    #[should_panic(expected = "Test inputs shouldn't cause compilation errors")]
-    fn test_infra_panic_when_test_input_contains_syntax_errors() {
-        run_compiler("syntax error here", |_tcx| panic!("This part shouldn't execute"))
+/// Parses a single type argument, given as a string of Rust syntax (e.g. `"i32"`), into a
+/// `Ty`. Only the primitive types `format_ty` already knows how to format are recognized,
+/// since those are the only types a monomorphized instantiation could produce bindings for.
+fn parse_type_arg<'tcx>(tcx: TyCtxt<'tcx>, type_arg: &str) -> Result<Ty<'tcx>> {
+    match type_arg.trim() {
+        "bool" => Ok(tcx.types.bool),
+        "char" => Ok(tcx.types.char),
+        "f32" => Ok(tcx.types.f32),
+        "f64" => Ok(tcx.types.f64),
+        "i8" => Ok(tcx.types.i8),
+        "i16" => Ok(tcx.types.i16),
+        "i32" => Ok(tcx.types.i32),
+        "i64" => Ok(tcx.types.i64),
+        "isize" => Ok(tcx.types.isize),
+        "u8" => Ok(tcx.types.u8),
+        "u16" => Ok(tcx.types.u16),
+        "u32" => Ok(tcx.types.u32),
+        "u64" => Ok(tcx.types.u64),
+        "usize" => Ok(tcx.types.usize),
+        other => bail!("Unrecognized or unsupported type argument: `{other}`"),
     }
+}
 
-    #[test]
-    #[should_panic(expected = "Test inputs shouldn't cause compilation errors")]
-    fn test_infra_panic_when_test_input_triggers_analysis_errors() {
-        run_compiler("#![feature(no_such_feature)]", |_tcx| panic!("This part shouldn't execute"))
+/// Checks that instantiating `def_id`'s generic parameters with `substs` satisfies all of
+/// the trait bounds (if any) that `def_id` declares on them -- e.g. so that a caller can't
+/// request `generic_function::<SomeType>` when `generic_function` requires `SomeType: Copy`
+/// but it isn't.
+fn ensure_substitution_satisfies_bounds<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    def_id: rustc_span::def_id::DefId,
+    substs: ty::SubstsRef<'tcx>,
+) -> Result<()> {
+    let param_env = ty::ParamEnv::reveal_all();
+    let predicates = tcx.predicates_of(def_id).instantiate(tcx, substs);
+
+    let infcx = tcx.infer_ctxt().build();
+    let ocx = ObligationCtxt::new(&infcx);
+    for predicate in predicates.predicates {
+        ocx.register_obligation(Obligation::new(
+            tcx,
+            ObligationCause::dummy(),
+            param_env,
+            predicate,
+        ));
+    }
+    let errors = ocx.select_all_or_error();
+    if !errors.is_empty() {
+        bail!(
+            "{} unsatisfied trait bound(s), e.g. `{:?}`",
+            errors.len(),
+            errors[0].obligation.predicate
+        );
     }
+    Ok(())
+}
 
-    #[test]
-    #[should_panic(expected = "Test inputs shouldn't cause compilation errors")]
-    fn test_infra_panic_when_test_input_triggers_warnings() {
-        run_compiler("pub fn foo(unused_parameter: i32) {}", |_tcx| {
-            panic!("This part shouldn't execute")
-        })
+/// Returns whether `ty` implements `trait_def_id`, under a reveal-all `ParamEnv` (`ty` is
+/// always a concrete, monomorphic type here -- there are no generic structs to reason about
+/// abstractly, since `format_adt` rejects those before this would ever be called).
+fn type_implements_trait<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>, trait_def_id: rustc_span::def_id::DefId) -> bool {
+    let param_env = ty::ParamEnv::reveal_all();
+    let infcx = tcx.infer_ctxt().build();
+    let ocx = ObligationCtxt::new(&infcx);
+    ocx.register_bound(ObligationCause::dummy(), param_env, ty, trait_def_id);
+    ocx.select_all_or_error().is_empty()
+}
+
+/// Formats a `#[repr(C)]`/`#[repr(transparent)]` struct definition as a matching C++
+/// `struct`, with fields kept in their Rust declaration order.
+///
+/// Rust's default (non-`repr(C)`) struct layout is unspecified, so anything else is
+/// rejected with a diagnostic explaining why.
+fn format_adt(
+    tcx: TyCtxt,
+    def_id: LocalDefId,
+    name: &Ident,
+    generics: &rustc_hir::Generics,
+    config: &BindingsConfig,
+) -> Result<ItemSnippet> {
+    if generics.params.len() > 0 {
+        // TODO(b/254099023): Consider supporting type/const-generic structs via
+        // monomorphization, similar to class template instantiations.
+        bail!("Generic structs are not supported yet (b/254099023)");
     }
 
-    #[test]
-    fn test_infra_nightly_features_ok_in_test_input() {
-        // This test arbitrarily picks `yeet_expr` as an example of a feature that
-        // hasn't yet been stabilized.
-        let test_src = r#"
-                // This test is supposed to test that *nightly* features are ok
-                // in the test input.  The `forbid` directive below helps to
-                // ensure that we'll realize in the future when the `yeet_expr`
-                // feature gets stabilized, making it not quite fitting for use
-                // in this test.
-                #![forbid(stable_features)]
+    let adt_def = tcx.adt_def(def_id.to_def_id());
+    if !adt_def.repr().c() && !adt_def.repr().transparent() {
+        bail!(
+            "Only structs annotated with `#[repr(C)]` or `#[repr(transparent)]` can be \
+             translated to a C++ struct, because Rust's default layout is unspecified"
+        );
+    }
 
-                #![feature(yeet_expr)]
-            "#;
-        run_compiler(test_src, |_tcx| ())
+    let cc_name = format_cc_ident(name.as_str()).context("Error formatting struct name")?;
+    let variant = adt_def.non_enum_variant();
+
+    let mut includes = BTreeSet::new();
+    let mut cc_fields = Vec::with_capacity(variant.fields.len());
+    // All fields stay in a single `public:` block, in Rust declaration order. The C++
+    // standard only guarantees relative layout *within* one access-specifier block --
+    // members across different blocks may be reordered relative to each other, so
+    // interleaving `private:`/`public:` here (to make non-`pub` fields inaccessible as
+    // `self_->field`) would be a layout assumption `validate_adt_layout` has no way to
+    // double-check, on top of the one it already makes. Non-`pub` fields are still *public
+    // in Rust's sense* an implementation detail: real encapsulation is enforced by the Rust
+    // visibility check at the call site that decides what gets bound at all, not by this
+    // mirror struct; the `__crubit_thunk_field_ptr_*` accessors from
+    // `collect_nested_field_bindings` are the sanctioned access path for generated bindings,
+    // but nothing stops arbitrary C++ from also reading `self_->field` directly.
+    for (i, field) in variant.fields.iter().enumerate() {
+        let field_ty = tcx.type_of(field.did);
+        let CcSnippet { includes: field_includes, tokens: field_cc_ty } =
+            format_ty(tcx, field_ty, config).with_context(|| {
+                format!("Error formatting the type of field #{i} (`{}`)", field.name)
+            })?;
+        includes.extend(field_includes);
+        let field_name = format_cc_field_ident(field.name.as_str())
+            .with_context(|| format!("Error formatting the name of field `{}`", field.name))?;
+        cc_fields.push(quote! { #field_cc_ty #field_name; });
     }
 
-    #[test]
-    fn test_infra_stabilized_features_ok_in_test_input() {
-        // This test arbitrarily picks `const_ptr_offset_from` as an example of a
-        // feature that has been already stabilized.
-        run_compiler("#![feature(const_ptr_offset_from)]", |_tcx| ())
+    let self_ty = tcx.type_of(def_id.to_def_id());
+    validate_adt_layout(tcx, self_ty, &variant.fields)
+        .with_context(|| format!("The generated C++ struct's layout wouldn't match `{}`'s Rust layout", name))?;
+
+    // A dedicated take-by-value thunk isn't generated yet -- `format_clone_thunk` only
+    // covers the "clone into a fresh slot" case that would build on.
+    let emplacement = format_emplacement_default(tcx, self_ty, name, &variant.fields)?;
+    let drop_thunk = format_drop_thunk(tcx, adt_def, name)?;
+    let clone_thunk = format_clone_thunk(tcx, self_ty, name)?;
+    let clone_from_thunk = format_clone_from_thunk(tcx, self_ty, name)?;
+
+    let rs_struct_name = format_rs_ident(name.as_str());
+    let mut offset_assertions = Vec::new();
+    let mut nested_field_bindings =
+        ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} };
+    collect_nested_field_bindings(
+        tcx,
+        &rs_struct_name,
+        &cc_name,
+        self_ty,
+        0,
+        &[],
+        config,
+        &mut offset_assertions,
+        &mut nested_field_bindings,
+    )?;
+
+    let rs_thunks = [
+        emplacement.rs_thunks,
+        drop_thunk.rs_thunks,
+        clone_thunk.rs_thunks,
+        clone_from_thunk.rs_thunks,
+        nested_field_bindings.rs_thunks,
+    ];
+    let thunk_decls = [
+        emplacement.cc.tokens,
+        drop_thunk.cc.tokens,
+        clone_thunk.cc.tokens,
+        clone_from_thunk.cc.tokens,
+        nested_field_bindings.cc.tokens,
+    ];
+    includes.extend(emplacement.cc.includes);
+    includes.extend(drop_thunk.cc.includes);
+    includes.extend(clone_thunk.cc.includes);
+    includes.extend(clone_from_thunk.cc.includes);
+    includes.extend(nested_field_bindings.cc.includes);
+
+    let special_members =
+        format_special_members(&cc_name, name, &emplacement, &drop_thunk, &clone_thunk, &clone_from_thunk);
+    if !emplacement.cc.tokens.is_empty() {
+        // The emplacement constructor below calls `std::abort()` if a field's
+        // `Default::default()` panicked mid-construction.
+        includes.insert("cstdlib");
     }
 
-    #[test]
-    #[should_panic(expected = "No items named `missing_name`.\n\
-                               Instead found:\n`bar`,\n`foo`,\n`m1`,\n`m2`,\n`std`")]
-    fn test_find_def_id_by_name_panic_when_no_item_with_matching_name() {
-        let test_src = r#"
-                pub extern "C" fn foo() {}
+    let struct_tokens = quote! { struct #cc_name { #(#cc_fields)* #(#special_members)* }; };
 
-                pub mod m1 {
-                    pub fn bar() {}
-                }
-                pub mod m2 {
-                    pub fn bar() {}
+    Ok(ItemSnippet {
+        cc: CcSnippet { includes, tokens: quote! { #struct_tokens #(#thunk_decls)* } },
+        rs_thunks: quote! { #(#rs_thunks)* #(#offset_assertions)* },
+    })
+}
+
+/// Wires the thunks `format_adt` already generated (emplacement, drop, clone, clone_from) into
+/// actual C++ special member functions on the mirror struct, so a plain `return`, scope exit,
+/// copy, or copy-assignment of the generated type actually runs the Rust semantics those
+/// thunks capture, instead of silently falling back to C++'s implicit (memberwise,
+/// non-cleanup-aware) versions.
+///
+/// Each thunk's `ItemSnippet` is empty (see e.g. `format_drop_thunk`) when the corresponding
+/// Rust trait/bound isn't present, so the matching special member is simply omitted here --
+/// a type with no `Drop` impl needs no user-declared destructor, etc.
+fn format_special_members(
+    cc_name: &TokenStream,
+    name: &Ident,
+    emplacement: &ItemSnippet,
+    drop_thunk: &ItemSnippet,
+    clone_thunk: &ItemSnippet,
+    clone_from_thunk: &ItemSnippet,
+) -> Vec<TokenStream> {
+    let mut members = Vec::new();
+
+    if !emplacement.cc.tokens.is_empty() {
+        let emplace_name = format_cc_ident(&format!("__crubit_thunk_emplace_default_{}", name.as_str()))
+            .expect("Already formatted once by `format_emplacement_default`");
+        members.push(quote! {
+            // In-place default-constructs via the emplacement thunk above, so this
+            // `!Unpin` type is never moved after construction.
+            #cc_name() {
+                if (!#emplace_name(this)) {
+                    // A field's `Default::default()` panicked mid-construction; there's no
+                    // way to propagate that out of a C++ constructor, so fail loudly.
+                    std::abort();
                 }
-            "#;
-        run_compiler(test_src, |tcx| find_def_id_by_name(tcx, "missing_name"));
+            }
+        });
     }
 
-    #[test]
-    #[should_panic(expected = "More than one item named `some_name`")]
-    fn test_find_def_id_by_name_panic_when_multiple_items_with_matching_name() {
-        let test_src = r#"
-                pub mod m1 {
-                    pub fn some_name() {}
+    if !clone_thunk.cc.tokens.is_empty() {
+        let clone_name = format_cc_ident(&format!("__crubit_thunk_clone_{}", name.as_str()))
+            .expect("Already formatted once by `format_clone_thunk`");
+        members.push(quote! {
+            #cc_name(#cc_name const& other) { #clone_name(&other, this); }
+        });
+
+        if !clone_from_thunk.cc.tokens.is_empty() {
+            let clone_from_name =
+                format_cc_ident(&format!("__crubit_thunk_clone_from_{}", name.as_str()))
+                    .expect("Already formatted once by `format_clone_from_thunk`");
+            members.push(quote! {
+                #cc_name& operator=(#cc_name const& other) {
+                    if (this != &other) { #clone_from_name(this, &other); }
+                    return *this;
                 }
-                pub mod m2 {
-                    pub fn some_name() {}
+            });
+        } else {
+            // No custom `clone_from` to call into -- match Rust's provided-method default
+            // (`*self = source.clone()`) by destroying `self` and cloning `other` in its place.
+            members.push(quote! {
+                #cc_name& operator=(#cc_name const& other) {
+                    if (this != &other) {
+                        this->~#cc_name();
+                        ::new (this) #cc_name(other);
+                    }
+                    return *this;
                 }
-            "#;
-        run_compiler(test_src, |tcx| find_def_id_by_name(tcx, "some_name"));
+            });
+        }
     }
 
-    #[test]
-    fn test_generated_bindings_fn_success() {
+    if !drop_thunk.cc.tokens.is_empty() {
+        let drop_name = format_cc_ident(&format!("__crubit_thunk_drop_{}", name.as_str()))
+            .expect("Already formatted once by `format_drop_thunk`");
+        members.push(quote! {
+            ~#cc_name() { #drop_name(this); }
+        });
+    }
+
+    members
+}
+
+/// If `self_ty` is `!Unpin` (so returning it by value -- constructing a whole `self_ty` and
+/// handing it back to the caller to place, the only return convention `format_fn_with_sig`
+/// supports today -- would be unsound: the move into the caller's storage could invalidate
+/// self-references the value holds), and it's constructible via `Default`, emits an
+/// in-place-initializer thunk that instead builds each field directly inside
+/// caller-provided, already-allocated storage, so `self_ty` is never moved after
+/// construction.
+///
+/// Only derived-shaped `Default` impls are handled: this works by calling
+/// `Default::default()` on each *field*'s type rather than on `self_ty` itself (calling
+/// `self_ty`'s own `Default::default()` would materialize a complete, movable value -- the
+/// exact thing we're trying to avoid), so it requires every field to independently
+/// implement `Default`. A hand-written `impl Default for self_ty` that doesn't decompose
+/// this way (e.g. one with cross-field invariants) is silently skipped; that's a real gap,
+/// but there's no general "pinned constructor" convention for this generator to recognize
+/// yet, and skipping is preferable to emitting an initializer that doesn't match the type's
+/// actual `Default` impl.
+///
+/// Returns an empty `ItemSnippet` (no declaration, no thunk) if `self_ty` is `Unpin`, or if
+/// it or any of its fields doesn't implement `Default`.
+fn format_emplacement_default<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    self_ty: Ty<'tcx>,
+    name: &Ident,
+    fields: &[ty::FieldDef],
+) -> Result<ItemSnippet> {
+    let unpin_trait = tcx.lang_items().unpin_trait().expect("`Unpin` is a lang item");
+    if type_implements_trait(tcx, self_ty, unpin_trait) {
+        // Movable: an ordinary by-value construct-and-return is sound here, so there's no
+        // need for this alternate emplacement path.
+        return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+    }
+
+    let default_trait = match tcx.get_diagnostic_item(rustc_span::sym::Default) {
+        Some(def_id) => def_id,
+        None => return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} }),
+    };
+    if !type_implements_trait(tcx, self_ty, default_trait) {
+        return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+    }
+
+    let mut field_locals = Vec::with_capacity(fields.len());
+    let mut field_accessors = Vec::with_capacity(fields.len());
+    let mut field_tys = Vec::with_capacity(fields.len());
+    for (i, field) in fields.iter().enumerate() {
+        let field_ty = tcx.type_of(field.did);
+        if !type_implements_trait(tcx, field_ty, default_trait) {
+            // See the doc comment: a non-derived-shaped `Default` impl can't be
+            // decomposed field-by-field, so there's no safe way to emplace it here.
+            return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+        }
+        // The local variable holding this field's freshly-built value needs its own name,
+        // distinct from the field's access path: a tuple-struct field's path segment is a
+        // bare integer literal (`0`), which can't appear on the left of a `let` binding. Named
+        // fields keep using their own name as the local, as before.
+        field_locals.push(match field.name.as_str().parse::<u32>() {
+            Ok(_) => format_rs_ident(&format!("field_{i}")),
+            Err(_) => format_rs_ident(field.name.as_str()),
+        });
+        field_accessors.push(format_rs_field_path_segment(field.name.as_str()));
+        field_tys.push(format_rs_ty_tokens(field_ty));
+    }
+
+    let rs_struct_name = format_rs_ident(name.as_str());
+    let thunk_name = format!("__crubit_thunk_emplace_default_{}", name.as_str());
+    let cc_thunk_name =
+        format_cc_ident(&thunk_name).context("Error formatting the name of the emplacement thunk")?;
+    let rs_thunk_name = format_rs_ident(&thunk_name);
+    let cc_name = format_cc_ident(name.as_str()).context("Error formatting struct name")?;
+
+    // Initializes each field in declaration order directly inside `*__ret_slot`, via
+    // `catch_unwind` around each field's `Default::default()` -- if field `i` panics, every
+    // field before it (already written into `*__ret_slot`) is dropped in reverse order and
+    // the thunk returns `false` with `*__ret_slot` left uninitialized; field `i` itself
+    // needs no separate cleanup, since unwinding out of the `catch_unwind`red closure has
+    // already run the destructors of whatever it allocated on its own stack.
+    let mut field_inits = Vec::with_capacity(field_locals.len());
+    for i in 0..field_locals.len() {
+        let field_local = &field_locals[i];
+        let field_accessor = &field_accessors[i];
+        let field_ty = &field_tys[i];
+        let unwind_fields = field_accessors[..i].iter().rev().map(|prior| {
+            quote! { ::std::ptr::drop_in_place(::std::ptr::addr_of_mut!((*__ret_slot).#prior)); }
+        });
+        field_inits.push(quote! {
+            let #field_local = match ::std::panic::catch_unwind(
+                ::std::panic::AssertUnwindSafe(|| <#field_ty as ::std::default::Default>::default()),
+            ) {
+                ::std::result::Result::Ok(value) => value,
+                ::std::result::Result::Err(_) => {
+                    #( #unwind_fields )*
+                    return false;
+                }
+            };
+            ::std::ptr::addr_of_mut!((*__ret_slot).#field_accessor).write(#field_local);
+        });
+    }
+
+    Ok(ItemSnippet {
+        cc: CcSnippet::no_includes(quote! {
+            // In-place initializer for `#cc_name`: unlike a by-value constructor, this
+            // writes every field directly into `__ret_slot` so that `#cc_name` (which is
+            // `!Unpin` on the Rust side) is never moved after construction. Returns `false`
+            // (leaving `*__ret_slot` uninitialized) if a field's `Default::default()` panics.
+            extern "C" bool #cc_thunk_name(#cc_name* __ret_slot);
+        }),
+        rs_thunks: quote! {
+            #[no_mangle]
+            pub extern "C" fn #rs_thunk_name(__ret_slot: *mut #rs_struct_name) -> bool {
+                unsafe {
+                    #( #field_inits )*
+                    true
+                }
+            }
+        },
+    })
+}
+
+/// Generates the statements run when a thunk's `catch_unwind`ed body panics: prints a
+/// message naming the thunk (whose name is also the symbol C++ calls), then aborts.
+///
+/// A Rust panic must never unwind across an `extern "C"` boundary (even under
+/// `-Cpanic=abort`, where it would merely abort anyway, leaving no chance to report which
+/// thunk was responsible) -- every generated thunk whose body can run arbitrary user code
+/// (a `Drop`/`Clone` impl, in particular) wraps that body in `catch_unwind` and funnels a
+/// caught panic through this helper.
+///
+/// TODO(b/254097221): Route through a user-registered panic handler instead of always
+/// aborting, once there's a `BindingsConfig` knob for it.
+fn format_panic_abort_stmt(thunk_name: &str) -> TokenStream {
+    quote! {
+        ::std::eprintln!(
+            "Rust panicked in `{}`; aborting rather than unwinding across the C++ FFI boundary",
+            #thunk_name,
+        );
+        ::std::process::abort();
+    }
+}
+
+/// If `self_ty` has a user-defined `Drop` impl, emits a thunk that runs it via
+/// `ptr::drop_in_place` on a caller-owned `*mut self_ty`, wrapped in `catch_unwind` so a
+/// panicking destructor aborts (see `format_panic_abort_stmt`) instead of unwinding into
+/// C++. Returns an empty `ItemSnippet` if `self_ty` has no explicit destructor -- the
+/// generated C++ struct is plain data, so a type with no `Drop` impl needs no thunk to
+/// tear it down.
+fn format_drop_thunk<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    adt_def: ty::AdtDef<'tcx>,
+    name: &Ident,
+) -> Result<ItemSnippet> {
+    if adt_def.destructor(tcx).is_none() {
+        return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+    }
+
+    let cc_name = format_cc_ident(name.as_str()).context("Error formatting struct name")?;
+    let rs_struct_name = format_rs_ident(name.as_str());
+    let thunk_name = format!("__crubit_thunk_drop_{}", name.as_str());
+    let cc_thunk_name =
+        format_cc_ident(&thunk_name).context("Error formatting the name of the drop thunk")?;
+    let rs_thunk_name = format_rs_ident(&thunk_name);
+    let abort_stmt = format_panic_abort_stmt(&thunk_name);
+
+    Ok(ItemSnippet {
+        cc: CcSnippet::no_includes(quote! {
+            extern "C" void #cc_thunk_name(#cc_name* self_);
+        }),
+        rs_thunks: quote! {
+            #[no_mangle]
+            pub extern "C" fn #rs_thunk_name(self_: *mut #rs_struct_name) {
+                let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| unsafe {
+                    ::std::ptr::drop_in_place(self_);
+                }));
+                if result.is_err() {
+                    #abort_stmt
+                }
+            }
+        },
+    })
+}
+
+/// The `OutRef` write-once out-reference type (see `format_out_ref_support`'s doc comment),
+/// as a path usable from generated thunk bodies.
+fn out_ref_path() -> TokenStream {
+    quote! { __crubit::OutRef }
+}
+
+/// Emits the `OutRef` support module shared by every "returns into a caller-provided slot"
+/// thunk (so far just `format_clone_thunk`; `format_emplacement_default`'s per-field,
+/// incremental writes don't fit `OutRef`'s "whole value, written exactly once" contract, so
+/// it keeps writing through its raw `__ret_slot: *mut Self` directly). Emitted once into
+/// `rs_body`, regardless of how many thunks end up using it.
+///
+/// `OutRef` itself doesn't change any thunk's `extern "C"` signature or ABI -- a thunk still
+/// takes a bare `*mut T` from C++, exactly as before -- it's wrapped in `OutRef::new` as the
+/// first thing the thunk body does, so that everything downstream of that point goes through
+/// `write`'s write-once, return-a-reference contract instead of a raw pointer write.
+///
+/// In debug builds, `OutRef` tracks whether `write` was actually called and panics on drop if
+/// not, so a thunk that returns early without writing (e.g. a future unwind-safety path that
+/// bails out before calling `write`) is caught loudly here rather than silently handing C++
+/// uninitialized memory. In release builds the tracking field is compiled out, so `OutRef` is
+/// exactly as cheap as the raw pointer it wraps.
+fn format_out_ref_support() -> TokenStream {
+    quote! {
+        #[doc(hidden)]
+        mod __crubit {
+            /// A write-once out-reference into caller-provided, possibly-uninitialized
+            /// storage for a value of type `T`. See `cc_bindings_from_rs::bindings::format_out_ref_support`.
+            pub struct OutRef<'a, T> {
+                slot: *mut T,
+                #[cfg(debug_assertions)]
+                written: bool,
+                _marker: ::std::marker::PhantomData<&'a mut ::std::mem::MaybeUninit<T>>,
+            }
+
+            impl<'a, T> OutRef<'a, T> {
+                /// # Safety
+                /// `slot` must be valid for writes of a `T` for the lifetime `'a`, and the
+                /// caller must not read from `*slot` until after `write` has been called.
+                pub unsafe fn new(slot: *mut T) -> Self {
+                    Self {
+                        slot,
+                        #[cfg(debug_assertions)]
+                        written: false,
+                        _marker: ::std::marker::PhantomData,
+                    }
+                }
+
+                /// Writes `value` into the slot, consuming this `OutRef` so it can't be
+                /// written again, and returns a reference to the now-initialized value.
+                pub fn write(#[allow(unused_mut)] mut self, value: T) -> &'a mut T {
+                    #[cfg(debug_assertions)]
+                    {
+                        self.written = true;
+                    }
+                    unsafe {
+                        self.slot.write(value);
+                        &mut *self.slot
+                    }
+                }
+            }
+
+            #[cfg(debug_assertions)]
+            impl<'a, T> ::std::ops::Drop for OutRef<'a, T> {
+                fn drop(&mut self) {
+                    assert!(
+                        self.written,
+                        "OutRef dropped without writing to its slot; caller would have \
+                         received uninitialized memory"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// If `self_ty` implements `Clone`, emits a thunk that clones `*self_` into a
+/// caller-provided, already-allocated `__ret_slot`, wrapped in `catch_unwind` (see
+/// `format_panic_abort_stmt`) so a panicking `Clone` impl aborts rather than unwinding into
+/// C++ with `__ret_slot` left partially written. Returns an empty `ItemSnippet` if `self_ty`
+/// doesn't implement `Clone`.
+fn format_clone_thunk<'tcx>(tcx: TyCtxt<'tcx>, self_ty: Ty<'tcx>, name: &Ident) -> Result<ItemSnippet> {
+    let clone_trait = match tcx.lang_items().clone_trait() {
+        Some(def_id) => def_id,
+        None => return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} }),
+    };
+    if !type_implements_trait(tcx, self_ty, clone_trait) {
+        return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+    }
+
+    let cc_name = format_cc_ident(name.as_str()).context("Error formatting struct name")?;
+    let rs_struct_name = format_rs_ident(name.as_str());
+    let thunk_name = format!("__crubit_thunk_clone_{}", name.as_str());
+    let cc_thunk_name =
+        format_cc_ident(&thunk_name).context("Error formatting the name of the clone thunk")?;
+    let rs_thunk_name = format_rs_ident(&thunk_name);
+    let abort_stmt = format_panic_abort_stmt(&thunk_name);
+    let out_ref = out_ref_path();
+
+    Ok(ItemSnippet {
+        cc: CcSnippet::no_includes(quote! {
+            extern "C" bool #cc_thunk_name(#cc_name const* self_, #cc_name* __ret_slot);
+        }),
+        rs_thunks: quote! {
+            #[no_mangle]
+            pub extern "C" fn #rs_thunk_name(
+                self_: *const #rs_struct_name,
+                __ret_slot: *mut #rs_struct_name,
+            ) -> bool {
+                let __ret_slot = unsafe { #out_ref::new(__ret_slot) };
+                match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| unsafe {
+                    ::std::clone::Clone::clone(&*self_)
+                })) {
+                    ::std::result::Result::Ok(value) => {
+                        __ret_slot.write(value);
+                        true
+                    }
+                    ::std::result::Result::Err(_) => {
+                        #abort_stmt
+                    }
+                }
+            }
+        },
+    })
+}
+
+/// Returns whether `self_ty`'s `Clone` impl overrides `clone_from` rather than inheriting
+/// `Clone`'s provided default (`fn clone_from(&mut self, source: &Self) { *self =
+/// source.clone(); }`) -- i.e. whether `clone_from` resolves to something other than that
+/// default method's own `DefId`.
+///
+/// Assumes `self_ty` is already known to implement `Clone` (checked by the caller).
+fn type_has_custom_clone_from<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    self_ty: Ty<'tcx>,
+    clone_trait: rustc_span::def_id::DefId,
+) -> Result<bool> {
+    let clone_from_default = tcx
+        .provided_trait_methods(clone_trait)
+        .iter()
+        .find(|item| item.name.as_str() == "clone_from")
+        .expect("`Clone::clone_from` is a provided trait method")
+        .def_id;
+
+    let param_env = ty::ParamEnv::reveal_all();
+    let substs = tcx.mk_substs_trait(self_ty, &[]);
+    let instance = ty::Instance::resolve(tcx, param_env, clone_from_default, substs)
+        .map_err(|_| anyhow::anyhow!("Error resolving `Clone::clone_from` for `{self_ty}`"))?
+        .with_context(|| format!("No `Clone::clone_from` implementation found for `{self_ty}`"))?;
+
+    Ok(instance.def_id() != clone_from_default)
+}
+
+/// If `self_ty`'s `Clone` impl overrides `clone_from` (see `type_has_custom_clone_from`),
+/// emits a thunk that calls it, so the generated C++ `operator=` binds to it and the
+/// optimization it exists for (typically reusing an existing heap allocation rather than
+/// dropping and reallocating one) survives the language boundary.
+///
+/// Otherwise -- no `Clone` impl at all, or (the common case for `#[derive(Clone)]`) a
+/// `clone_from` that's just the provided default, `*self = source.clone()` -- returns an
+/// empty `ItemSnippet`. That default offers nothing over destroying `self` (via the `drop`
+/// thunk) and constructing a fresh clone in its place (via `format_clone_thunk`), so there's
+/// no reason to force every `Clone` type through a distinct, always-present copy-assignment
+/// thunk just to re-derive what those two already give C++.
+fn format_clone_from_thunk<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    self_ty: Ty<'tcx>,
+    name: &Ident,
+) -> Result<ItemSnippet> {
+    let clone_trait = match tcx.lang_items().clone_trait() {
+        Some(def_id) => def_id,
+        None => return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} }),
+    };
+    if !type_implements_trait(tcx, self_ty, clone_trait) {
+        return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+    }
+    if !type_has_custom_clone_from(tcx, self_ty, clone_trait)? {
+        return Ok(ItemSnippet { cc: CcSnippet::no_includes(quote! {}), rs_thunks: quote! {} });
+    }
+
+    let cc_name = format_cc_ident(name.as_str()).context("Error formatting struct name")?;
+    let rs_struct_name = format_rs_ident(name.as_str());
+    let thunk_name = format!("__crubit_thunk_clone_from_{}", name.as_str());
+    let cc_thunk_name =
+        format_cc_ident(&thunk_name).context("Error formatting the name of the clone_from thunk")?;
+    let rs_thunk_name = format_rs_ident(&thunk_name);
+    let abort_stmt = format_panic_abort_stmt(&thunk_name);
+
+    Ok(ItemSnippet {
+        cc: CcSnippet::no_includes(quote! {
+            extern "C" void #cc_thunk_name(#cc_name* self_, #cc_name const* source);
+        }),
+        rs_thunks: quote! {
+            #[no_mangle]
+            pub extern "C" fn #rs_thunk_name(
+                self_: *mut #rs_struct_name,
+                source: *const #rs_struct_name,
+            ) {
+                let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| unsafe {
+                    ::std::clone::Clone::clone_from(&mut *self_, &*source)
+                }));
+                if result.is_err() {
+                    #abort_stmt
+                }
+            }
+        },
+    })
+}
+
+/// Returns `offset` rounded up to the next multiple of `align` (a power of two), matching
+/// how both Rust's `repr(C)` and a C++ compiler insert inter-field padding.
+fn round_up_to(offset: u64, align: u64) -> u64 {
+    (offset + align - 1) / align * align
+}
+
+/// Checks that `ty`'s actual (`repr(C)`) Rust layout matches the layout a C++ compiler
+/// would give the struct `format_adt` is about to emit for it -- i.e. that `fields`, laid
+/// out in declaration order with each field keeping its own natural size/alignment,
+/// reproduces `ty`'s size, alignment, and per-field offsets.
+///
+/// This should always hold for `#[repr(C)]` (that's the attribute's whole purpose), but
+/// there's no C++ compiler on hand to double-check against, so this is a defense-in-depth
+/// guard against a field type whose Rust and C++ layouts quietly diverge (e.g. a future
+/// `format_ty`-supported type that isn't laid out the same way on both sides).
+fn validate_adt_layout(tcx: TyCtxt, ty: Ty, fields: &[ty::FieldDef]) -> Result<()> {
+    let param_env = ty::ParamEnv::reveal_all();
+    let actual_layout = tcx
+        .layout_of(param_env.and(ty))
+        .map_err(|err| anyhow::anyhow!("Error computing the layout of `{ty}`: {err}"))?
+        .layout;
+
+    let mut expected_offset = 0u64;
+    let mut expected_align = 1u64;
+    for (i, field) in fields.iter().enumerate() {
+        let field_ty = tcx.type_of(field.did);
+        let field_layout = tcx
+            .layout_of(param_env.and(field_ty))
+            .map_err(|err| anyhow::anyhow!("Error computing the layout of `{field_ty}`: {err}"))?
+            .layout;
+        let field_size = field_layout.size.bytes();
+        let field_align = field_layout.align.abi.bytes();
+        expected_offset = round_up_to(expected_offset, field_align);
+
+        let actual_offset = actual_layout.fields.offset(i).bytes();
+        if actual_offset != expected_offset {
+            bail!(
+                "Field #{i} (`{}`) is at offset {actual_offset}, but the generated C++ \
+                 struct would place it at offset {expected_offset}",
+                field.name
+            );
+        }
+        expected_offset += field_size;
+        expected_align = expected_align.max(field_align);
+    }
+    expected_offset = round_up_to(expected_offset, expected_align);
+
+    let actual_size = actual_layout.size.bytes();
+    if actual_size != expected_offset {
+        bail!(
+            "`{ty}` has size {actual_size}, but the generated C++ struct would have size \
+             {expected_offset}"
+        );
+    }
+    let actual_align = actual_layout.align.abi.bytes();
+    if actual_align != expected_align {
+        bail!(
+            "`{ty}` has alignment {actual_align}, but the generated C++ struct would have \
+             alignment {expected_align}"
+        );
+    }
+    Ok(())
+}
+
+/// Recursively walks `parent_ty`'s fields (as `validate_adt_layout` does, but continuing
+/// into any field that is itself a `#[repr(C)]`/`#[repr(transparent)]` struct -- the only
+/// kind of nested field `format_ty` allows), collecting:
+///
+///   * one `const _: () = assert!(core::mem::offset_of!(#root_ident, a.b.c) == N);` per
+///     reachable field, at every depth, pinning down its exact absolute byte offset from
+///     `root_ident`'s own start so field-reordering in a nested `repr(Rust)`-like member
+///     (nothing here is actually `repr(Rust)`, since only `repr(C)`/`repr(transparent)`
+///     structs are accepted at all, but a *future* field type with a less rigid layout
+///     contract would be caught by this) can't silently break the binding; and
+///   * a pair of `__crubit_thunk_field_ptr_<path>`/`__crubit_thunk_field_ptr_const_<path>`
+///     accessor thunks for each field that isn't `pub` (a `pub` field's C++ counterpart is
+///     already directly accessible as a member of the mirrored C++ struct, so it needs no
+///     thunk), letting C++ project into it without hardcoding the offset itself.
+///
+/// `base_offset` is `parent_ty`'s own absolute byte offset from `root_ident`'s start (`0`
+/// for the initial, top-level call), and `path` is the dotted field-name path leading from
+/// `root_ident` to `parent_ty` (empty for the initial call). Each path segment is either a
+/// named field (`foo`) or a tuple-struct index (`0`) -- both `offset_of!` and ordinary field
+/// access accept either kind of segment interchangeably (e.g. `offset_of!(S, a.0.b)`), so a
+/// single `TokenStream` per segment covers both.
+fn collect_nested_field_bindings<'tcx>(
+    tcx: TyCtxt<'tcx>,
+    root_ident: &proc_macro2::Ident,
+    root_cc_name: &proc_macro2::Ident,
+    parent_ty: Ty<'tcx>,
+    base_offset: u64,
+    path: &[TokenStream],
+    config: &BindingsConfig,
+    assertions: &mut Vec<TokenStream>,
+    accessors: &mut ItemSnippet,
+) -> Result<()> {
+    let ty::TyKind::Adt(adt_def, substs) = parent_ty.kind() else {
+        // Not itself a (non-generic) struct -- e.g. a primitive leaf field -- so there's
+        // nothing further to walk into.
+        return Ok(());
+    };
+    if !substs.is_empty() || !adt_def.is_struct() {
+        return Ok(());
+    }
+
+    let param_env = ty::ParamEnv::reveal_all();
+    let parent_layout = tcx
+        .layout_of(param_env.and(parent_ty))
+        .map_err(|err| anyhow::anyhow!("Error computing the layout of `{parent_ty}`: {err}"))?
+        .layout;
+    let variant = adt_def.non_enum_variant();
+
+    for (i, field) in variant.fields.iter().enumerate() {
+        let field_ty = tcx.type_of(field.did);
+        let field_ident = format_rs_field_path_segment(field.name.as_str());
+        let mut field_path = path.to_vec();
+        field_path.push(field_ident.clone());
+        let field_offset = base_offset + parent_layout.fields.offset(i).bytes();
+
+        assertions.push(quote! {
+            const _: () = assert!(
+                ::core::mem::offset_of!(#root_ident, #(#field_path).*) == #field_offset
+            );
+        });
+
+        if !field.vis.is_public() {
+            let CcSnippet { includes: field_includes, tokens: field_cc_ty } =
+                format_ty(tcx, field_ty, config).with_context(|| {
+                    format!("Error formatting the type of field `{}`", field.name)
+                })?;
+            let rs_field_ty = format_rs_ty_tokens(field_ty);
+            let path_suffix = field_path.iter().map(|ident| ident.to_string()).join("_");
+            let mut_thunk_name = format!("__crubit_thunk_field_ptr_{path_suffix}");
+            let const_thunk_name = format!("__crubit_thunk_field_ptr_const_{path_suffix}");
+            let cc_mut_thunk_name = format_cc_ident(&mut_thunk_name)
+                .context("Error formatting the name of the field accessor thunk")?;
+            let cc_const_thunk_name = format_cc_ident(&const_thunk_name)
+                .context("Error formatting the name of the field accessor thunk")?;
+            let rs_mut_thunk_name = format_rs_ident(&mut_thunk_name);
+            let rs_const_thunk_name = format_rs_ident(&const_thunk_name);
+
+            accessors.cc.includes.extend(field_includes);
+            accessors.cc.tokens.extend(quote! {
+                extern "C" #field_cc_ty* #cc_mut_thunk_name(#root_cc_name* self_);
+                extern "C" #field_cc_ty const* #cc_const_thunk_name(#root_cc_name const* self_);
+            });
+            accessors.rs_thunks.extend(quote! {
+                #[no_mangle]
+                pub extern "C" fn #rs_mut_thunk_name(self_: *mut #root_ident) -> *mut #rs_field_ty {
+                    unsafe { ::std::ptr::addr_of_mut!((*self_).#(#field_path).*) }
+                }
+                #[no_mangle]
+                pub extern "C" fn #rs_const_thunk_name(self_: *const #root_ident) -> *const #rs_field_ty {
+                    unsafe { ::std::ptr::addr_of!((*self_).#(#field_path).*) }
+                }
+            });
+        }
+
+        collect_nested_field_bindings(
+            tcx,
+            root_ident,
+            root_cc_name,
+            field_ty,
+            field_offset,
+            &field_path,
+            config,
+            assertions,
+            accessors,
+        )?;
+    }
+    Ok(())
+}
+
+/// Formats a C++ comment explaining why no bindings have been generated for
+/// `local_def_id`.
+fn format_unsupported_def(
+    tcx: TyCtxt,
+    local_def_id: LocalDefId,
+    err: anyhow::Error,
+) -> TokenStream {
+    let span = tcx.sess().source_map().span_to_embeddable_string(tcx.def_span(local_def_id));
+    let name = tcx.def_path_str(local_def_id.to_def_id());
+
+    // https://docs.rs/anyhow/latest/anyhow/struct.Error.html#display-representations
+    // says: To print causes as well [...], use the alternate selector “{:#}”.
+    let msg = format!("Error generating bindings for `{name}` defined at {span}: {err:#}");
+    quote! { __NEWLINE__ __NEWLINE__ __COMMENT__ #msg __NEWLINE__ }
+}
+
+/// Formats all the public items of a single Rust module (given as the
+/// `item_ids` of its children), recursing into any nested `mod`s and emitting
+/// a nested C++ `namespace` for each one.
+fn format_mod_children(
+    tcx: TyCtxt,
+    item_ids: &[rustc_hir::ItemId],
+    config: &BindingsConfig,
+) -> ItemSnippet {
+    let mut includes = BTreeSet::new();
+    let mut cc_tokens = Vec::new();
+    let mut rs_thunks = Vec::new();
+
+    for item_id in item_ids {
+        let item = tcx.hir().item(*item_id);
+        let def_id = item.def_id.def_id;
+
+        // Skip non-public items; they have no bindings to generate and (for
+        // `mod`s) no need to be recursed into.
+        if !tcx.visibility(def_id.to_def_id()).is_public() {
+            continue;
+        }
+
+        let snippet = match &item.kind {
+            ItemKind::Mod(module) => match format_cc_ident(item.ident.as_str()) {
+                Ok(mod_name) => {
+                    let ItemSnippet { cc: CcSnippet { includes: mod_includes, tokens: mod_cc }, rs_thunks: mod_rs } =
+                        format_mod_children(tcx, module.item_ids, config);
+                    ItemSnippet {
+                        cc: CcSnippet {
+                            includes: mod_includes,
+                            tokens: quote! { namespace #mod_name { #mod_cc } },
+                        },
+                        rs_thunks: mod_rs,
+                    }
+                }
+                Err(err) => ItemSnippet {
+                    cc: CcSnippet::no_includes(format_unsupported_def(tcx, def_id, err)),
+                    rs_thunks: quote! {},
+                },
+            },
+            _ => match format_def(tcx, def_id, config) {
+                Ok(snippet) => snippet,
+                Err(err) => ItemSnippet {
+                    cc: CcSnippet::no_includes(format_unsupported_def(tcx, def_id, err)),
+                    rs_thunks: quote! {},
+                },
+            },
+        };
+
+        includes.extend(snippet.cc.includes);
+        cc_tokens.push(snippet.cc.tokens);
+        rs_thunks.push(snippet.rs_thunks);
+    }
+
+    ItemSnippet {
+        cc: CcSnippet { includes, tokens: quote! { #( #cc_tokens )* } },
+        rs_thunks: quote! { #( #rs_thunks )* },
+    }
+}
+
+/// Formats all public items from the Rust crate being compiled (aka the
+/// `LOCAL_CRATE`), by walking the crate's module tree top-down starting from
+/// the crate root.
+fn format_crate(tcx: TyCtxt, config: &BindingsConfig) -> Result<ItemSnippet> {
+    let crate_name = format_cc_ident(tcx.crate_name(LOCAL_CRATE).as_str())?;
+    let ItemSnippet { cc: CcSnippet { includes, tokens: crate_content }, rs_thunks } =
+        format_mod_children(tcx, tcx.hir().root_module().item_ids, config);
+
+    Ok(ItemSnippet {
+        cc: CcSnippet {
+            includes,
+            tokens: quote! {
+                namespace #crate_name {
+                    #crate_content
+                }
+            },
+        },
+        rs_thunks,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{
+        format_def, format_target_feature_attr, format_ty, BindingsConfig, CcSnippet,
+        GeneratedBindings, Int128Backend, ItemSnippet,
+    };
+
+    use anyhow::Result;
+    use itertools::Itertools;
+    use proc_macro2::TokenStream;
+    use quote::quote;
+    use rustc_middle::ty::{Ty, TyCtxt};
+    use rustc_span::def_id::LocalDefId;
+    use std::path::PathBuf;
+
+    use token_stream_matchers::{
+        assert_cc_matches, assert_cc_not_matches, assert_rs_matches, assert_rs_not_matches,
+    };
+
+    pub fn get_sysroot_for_testing() -> PathBuf {
+        let runfiles = runfiles::Runfiles::create().unwrap();
+        runfiles.rlocation(if std::env::var("LEGACY_TOOLCHAIN_RUST_TEST").is_ok() {
+            "google3/third_party/unsupported_toolchains/rust/toolchains/nightly"
+        } else {
+            "google3/nowhere/llvm/rust"
+        })
+    }
+
+    #[test]
+    #[should_panic(expected = "Test inputs shouldn't cause compilation errors")]
+    fn test_infra_panic_when_test_input_contains_syntax_errors() {
+        run_compiler("syntax error here", |_tcx| panic!("This part shouldn't execute"))
+    }
+
+    #[test]
+    #[should_panic(expected = "Test inputs shouldn't cause compilation errors")]
+    fn test_infra_panic_when_test_input_triggers_analysis_errors() {
+        run_compiler("#![feature(no_such_feature)]", |_tcx| panic!("This part shouldn't execute"))
+    }
+
+    #[test]
+    #[should_panic(expected = "Test inputs shouldn't cause compilation errors")]
+    fn test_infra_panic_when_test_input_triggers_warnings() {
+        run_compiler("pub fn foo(unused_parameter: i32) {}", |_tcx| {
+            panic!("This part shouldn't execute")
+        })
+    }
+
+    #[test]
+    fn test_infra_nightly_features_ok_in_test_input() {
+        // This test arbitrarily picks `yeet_expr` as an example of a feature that
+        // hasn't yet been stabilized.
+        let test_src = r#"
+                // This test is supposed to test that *nightly* features are ok
+                // in the test input.  The `forbid` directive below helps to
+                // ensure that we'll realize in the future when the `yeet_expr`
+                // feature gets stabilized, making it not quite fitting for use
+                // in this test.
+                #![forbid(stable_features)]
+
+                #![feature(yeet_expr)]
+            "#;
+        run_compiler(test_src, |_tcx| ())
+    }
+
+    #[test]
+    fn test_infra_stabilized_features_ok_in_test_input() {
+        // This test arbitrarily picks `const_ptr_offset_from` as an example of a
+        // feature that has been already stabilized.
+        run_compiler("#![feature(const_ptr_offset_from)]", |_tcx| ())
+    }
+
+    #[test]
+    #[should_panic(expected = "No items named `missing_name`.\n\
+                               Instead found:\n`bar`,\n`foo`,\n`m1`,\n`m2`,\n`std`")]
+    fn test_find_def_id_by_name_panic_when_no_item_with_matching_name() {
+        let test_src = r#"
+                pub extern "C" fn foo() {}
+
+                pub mod m1 {
+                    pub fn bar() {}
+                }
+                pub mod m2 {
+                    pub fn bar() {}
+                }
+            "#;
+        run_compiler(test_src, |tcx| find_def_id_by_name(tcx, "missing_name"));
+    }
+
+    #[test]
+    #[should_panic(expected = "More than one item named `some_name`")]
+    fn test_find_def_id_by_name_panic_when_multiple_items_with_matching_name() {
+        let test_src = r#"
+                pub mod m1 {
+                    pub fn some_name() {}
+                }
+                pub mod m2 {
+                    pub fn some_name() {}
+                }
+            "#;
+        run_compiler(test_src, |tcx| find_def_id_by_name(tcx, "some_name"));
+    }
+
+    #[test]
+    fn test_generated_bindings_fn_success() {
         // This test covers only a single example of a function that should get a C++
         // binding. Additional coverage of how items are formatted is provided by
         // `test_format_def_...` tests.
         let test_src = r#"
-                pub extern "C" fn public_function() {
-                    println!("foo");
+                pub extern "C" fn public_function() {
+                    println!("foo");
+                }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    extern "C" void public_function();
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_generated_bindings_fn_non_pub() {
+        let test_src = r#"
+                #![allow(dead_code)]
+                extern "C" fn private_function() {
+                    println!("foo");
+                }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+
+            // Non-public functions should not be present in the generated bindings.
+            assert_cc_not_matches!(bindings.h_body, quote! { private_function });
+        });
+    }
+
+    #[test]
+    fn test_generated_bindings_top_level_items() {
+        let test_src = "pub fn public_function() {}";
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            let expected_comment_txt =
+                "Automatically @generated C++ bindings for the following Rust crate:\n\
+                 rust_out";
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    __COMMENT__ #expected_comment_txt
+                    ...
+                    __HASH_TOKEN__ pragma once
+                    ...
+                    namespace rust_out {
+                        ...
+                    }
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn test_generated_bindings_nested_modules_become_nested_namespaces() {
+        let test_src = r#"
+                pub mod m1 {
+                    pub extern "C" fn bar() {}
+                }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    namespace rust_out {
+                        namespace m1 {
+                            extern "C" void bar();
+                        }
+                    }
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn test_generated_bindings_private_module_is_omitted() {
+        let test_src = r#"
+                #![allow(dead_code)]
+                mod m1 {
+                    pub extern "C" fn bar() {}
+                }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_cc_not_matches!(bindings.h_body, quote! { bar });
+        })
+    }
+
+    #[test]
+    fn test_generated_bindings_rs_body_has_thunk_for_non_c_abi_fn() {
+        let test_src = "pub fn public_function() -> i32 { 42 }";
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    extern "C" std::int32_t __crubit_thunk_public_function();
+                    inline std::int32_t public_function() {
+                        return __crubit_thunk_public_function();
+                    }
+                }
+            );
+            assert_rs_matches!(
+                bindings.rs_body,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_public_function() -> i32 {
+                        public_function()
+                    }
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn test_generated_bindings_rs_body_has_out_ref_support_when_clone_thunk_is_emitted() {
+        let test_src = r#"
+                #[repr(C)]
+                #[derive(Clone)]
+                pub struct HasClone {
+                    pub x: i32,
+                }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_rs_matches!(
+                bindings.rs_body,
+                quote! {
+                    mod __crubit {
+                        pub struct OutRef<'a, T> {
+                            ...
+                        }
+                        ...
+                    }
+                }
+            );
+            assert_rs_matches!(
+                bindings.rs_body,
+                quote! { let __ret_slot = unsafe { __crubit::OutRef::new(__ret_slot) }; }
+            );
+        })
+    }
+
+    #[test]
+    fn test_generated_bindings_rs_body_has_no_out_ref_support_when_unused() {
+        let test_src = r#"
+                #[repr(C)]
+                pub struct Plain {
+                    pub x: i32,
+                }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_rs_not_matches!(bindings.rs_body, quote! { mod __crubit });
+        })
+    }
+
+    #[test]
+    fn test_generated_bindings_dedupes_includes() {
+        // Two functions that each need `<cstdint>` should only `#include` it once.
+        let test_src = r#"
+                pub extern "C" fn get_i32() -> i32 { 42 }
+                pub extern "C" fn get_u32() -> u32 { 42 }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    __HASH_TOKEN__ pragma once
+                    ...
+                    __HASH_TOKEN__ include <cstdint>
+                    ...
+                    namespace rust_out {
+                        ...
+                    }
+                }
+            );
+            // `<cstdint>` should appear only once, even though two functions need it.
+            assert_eq!(bindings.h_body.to_string().matches("include <cstdint>").count(), 1);
+        });
+    }
+
+    #[test]
+    fn test_generated_bindings_no_include_for_char() {
+        // Unlike the fixed-width integers, `char32_t` is a standalone C++11 builtin type,
+        // so a function that only deals in `char` shouldn't pull in `<cstdint>`.
+        let test_src = r#"
+                pub extern "C" fn get_char() -> char { 'x' }
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    extern "C" char32_t get_char();
+                }
+            );
+            assert_eq!(bindings.h_body.to_string().matches("include <cstdint>").count(), 0);
+        });
+    }
+
+    #[test]
+    fn test_generated_bindings_unsupported_item() {
+        // This test verifies how `Err` from `format_def` is formatted as a C++ comment
+        // (in `format_crate` and `format_unsupported_def`).
+        // - This test covers only a single example of an unsupported item.  Additional
+        //   coverage is provided by `test_format_def_unsupported_...` tests.
+        // - This test somewhat arbitrarily chooses an example of an unsupported item,
+        //   trying to pick one that 1) will never be supported (b/254104998 has some extra
+        //   notes about APIs named after reserved C++ keywords) and 2) tests that the
+        //   full error chain is included in the message.
+        let test_src = r#"
+                pub extern "C" fn reinterpret_cast() {}
+            "#;
+        test_generated_bindings(test_src, |bindings| {
+            let bindings = bindings.expect("Test expects success");
+            let expected_comment_txt = "Error generating bindings for `reinterpret_cast` \
+                 defined at <crubit_unittests.rs>:2:17: 2:53: \
+                 Error formatting function name: \
+                 `reinterpret_cast` is a C++ reserved keyword \
+                 and can't be used as a C++ identifier";
+            assert_cc_matches!(
+                bindings.h_body,
+                quote! {
+                    __COMMENT__ #expected_comment_txt
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn test_format_def_fn_extern_c_no_params_no_return_type() {
+        let test_src = r#"
+                pub extern "C" fn public_function() {}
+            "#;
+        test_format_def(test_src, "public_function", |result| {
+            assert_cc_matches!(
+                result.expect("Test expects success here").cc.tokens,
+                quote! {
+                    extern "C" void public_function();
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_fn_extern_c_no_params_unit_return_type() {
+        // This test is very similar to the
+        // `test_format_def_fn_extern_c_no_params_no_return_type` above, except
+        // that the return type is explicitly spelled out.  There is no difference in
+        // `ty::FnSig` so our code behaves exactly the same, but the test has been
+        // planned based on earlier, hir-focused approach and having this extra
+        // test coverage shouldn't hurt. (`hir::FnSig` and `hir::FnRetTy` _do_
+        // see a difference between the two tests).
+        let test_src = r#"
+                pub extern "C" fn explicit_unit_return_type() -> () {}
+            "#;
+        test_format_def(test_src, "explicit_unit_return_type", |result| {
+            assert_cc_matches!(
+                result.expect("Test expects success here").cc.tokens,
+                quote! {
+                    extern "C" void explicit_unit_return_type();
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_fn_returning_integer() {
+        let test_src = r#"
+                pub extern "C" fn get_42() -> i32 { 42 }
+            "#;
+        test_format_def(test_src, "get_42", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" std::int32_t get_42();
                 }
+            );
+            assert_eq!(snippet.cc.includes.into_iter().collect::<Vec<_>>(), vec!["cstdint"]);
+        });
+    }
+
+    #[test]
+    fn test_format_def_unsupported_fn_unsafe() {
+        // This tests how bindings for an `unsafe fn` are generated.
+        let test_src = r#"
+                pub unsafe extern "C" fn foo() {}
             "#;
-        test_generated_bindings(test_src, |bindings| {
-            let bindings = bindings.expect("Test expects success");
+        test_format_def(test_src, "foo", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert_eq!(
+                err,
+                "Bindings for `unsafe` functions \
+                             are not fully designed yet (b/254095482)"
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_fn_const() {
+        // A `const fn` gets the same thunk-based bindings as any other non-`extern
+        // "C"` function (see `test_format_def_fn_non_c_abi_generates_thunk`), except
+        // the inline C++ wrapper is additionally marked `constexpr` so that callers
+        // can use it in constant expressions.
+        let test_src = r#"
+                pub const fn foo(i: i32) -> i32 { i * 42 }
+            "#;
+        test_format_def(test_src, "foo", |result| {
+            let snippet = result.expect("Test expects success here");
             assert_cc_matches!(
-                bindings.h_body,
+                snippet.cc.tokens,
                 quote! {
-                    extern "C" void public_function();
+                    extern "C" std::int32_t __crubit_thunk_foo(std::int32_t i);
+                    inline constexpr std::int32_t foo(std::int32_t i) {
+                        return __crubit_thunk_foo(i);
+                    }
                 }
             );
         });
     }
 
     #[test]
-    fn test_generated_bindings_fn_non_pub() {
+    fn test_format_def_fn_with_c_unwind_abi() {
+        // See also https://rust-lang.github.io/rfcs/2945-c-unwind-abi.html
         let test_src = r#"
-                #![allow(dead_code)]
-                extern "C" fn private_function() {
-                    println!("foo");
+                #![feature(c_unwind)]
+                pub extern "C-unwind" fn may_throw() {}
+            "#;
+        test_format_def(test_src, "may_throw", |result| {
+            assert_cc_matches!(
+                result.expect("Test expects success here").cc.tokens,
+                quote! {
+                    extern "C" void may_throw();
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_fn_with_type_aliased_return_type() {
+        // Type aliases disappear at the `rustc_middle::ty::Ty` level and therefore in
+        // the short-term the generated bindings also ignore type aliases.
+        //
+        // TODO(b/254096006): Consider preserving `type` aliases when generating
+        // bindings.
+        let test_src = r#"
+                type MyTypeAlias = f64;
+
+                pub extern "C" fn type_aliased_return() -> MyTypeAlias { 42.0 }
+            "#;
+        test_format_def(test_src, "type_aliased_return", |result| {
+            assert_cc_matches!(
+                result.expect("Test expects success here").cc.tokens,
+                quote! {
+                    extern "C" double type_aliased_return();
                 }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_unsupported_fn_name_is_reserved_cpp_keyword() {
+        let test_src = r#"
+                pub extern "C" fn reinterpret_cast() -> () {}
             "#;
-        test_generated_bindings(test_src, |bindings| {
-            let bindings = bindings.expect("Test expects success");
+        test_format_def(test_src, "reinterpret_cast", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert_eq!(
+                err,
+                "Error formatting function name: \
+                       `reinterpret_cast` is a C++ reserved keyword \
+                       and can't be used as a C++ identifier"
+            );
+        });
+    }
 
-            // Non-public functions should not be present in the generated bindings.
-            assert_cc_not_matches!(bindings.h_body, quote! { private_function });
+    #[test]
+    fn test_format_def_unsupported_fn_ret_type() {
+        let test_src = r#"
+                pub fn foo() -> (i32, i32) { (42, 42) }
+            "#;
+        test_format_def(test_src, "foo", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert_eq!(
+                err,
+                "Error formatting function return type: \
+                       Tuples are not supported yet: (i32, i32) (b/254097223)"
+            );
         });
     }
 
     #[test]
-    fn test_generated_bindings_top_level_items() {
-        let test_src = "pub fn public_function() {}";
-        test_generated_bindings(test_src, |bindings| {
-            let bindings = bindings.expect("Test expects success");
-            let expected_comment_txt =
-                "Automatically @generated C++ bindings for the following Rust crate:\n\
-                 rust_out";
+    fn test_format_def_fn_with_late_bound_lifetimes_erases_them() {
+        let test_src = r#"
+                pub fn foo(arg: &i32) -> &i32 { arg }
+
+                // Lifetime inference translates the above into:
+                //     pub fn foo<'a>(arg: &'a i32) -> &'a i32 { ... }
+                // leaving 'a lifetime late-bound (it is bound with a lifetime
+                // taken from each of the callsites).  `format_fn` erases it via
+                // `liberate_late_bound_regions` rather than rejecting the function.
+            "#;
+        test_format_def(test_src, "foo", |result| {
+            let snippet = result.expect("Test expects success here");
             assert_cc_matches!(
-                bindings.h_body,
+                snippet.cc.tokens,
                 quote! {
-                    __COMMENT__ #expected_comment_txt
-                    ...
-                    __HASH_TOKEN__ pragma once
-                    ...
-                    namespace rust_out {
-                        ...
+                    extern "C" const std::int32_t & __crubit_thunk_foo(const std::int32_t & arg);
+                    inline const std::int32_t & foo(const std::int32_t & arg) {
+                        return __crubit_thunk_foo(arg);
+                    }
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_foo(arg: &i32) -> &i32 {
+                        foo(arg)
+                    }
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_unsupported_generic_fn() {
+        let test_src = r#"
+                use std::default::Default;
+                use std::fmt::Display;
+                pub fn generic_function<T: Default + Display>() {
+                    println!("{}", T::default());
+                }
+            "#;
+        test_format_def(test_src, "generic_function", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert_eq!(
+                err,
+                "Generic functions (lifetime-generic or type-generic) are not supported yet, \
+                 unless the caller requests specific instantiations via `BindingsConfig`"
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_generic_fn_instantiation_via_config() {
+        let test_src = r#"
+                pub fn generic_function<T: Default>() -> T {
+                    T::default()
+                }
+            "#;
+        let mut config = BindingsConfig::new();
+        config.add_instantiation("generic_function", vec!["i32".to_string()]);
+        test_format_def_with_config(test_src, "generic_function", &config, |result| {
+            let snippet = result.unwrap();
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" std::int32_t __crubit_thunk_generic_function__i32();
+                    inline std::int32_t generic_function__i32() {
+                        return __crubit_thunk_generic_function__i32();
+                    }
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_generic_function__i32() -> i32 {
+                        generic_function::<i32>()
+                    }
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_generic_fn_instantiation_skipped_when_bounds_unsatisfied() {
+        // `f32` doesn't implement `Eq`, so this instantiation's trait bound isn't satisfied.
+        let test_src = r#"
+                pub fn generic_function<T: Eq>(_arg: T) {}
+            "#;
+        let mut config = BindingsConfig::new();
+        config.add_instantiation("generic_function", vec!["f32".to_string()]);
+        test_format_def_with_config(test_src, "generic_function", &config, |result| {
+            // The bad instantiation is skipped (reported as a comment), rather than
+            // failing the whole item -- there just aren't any other requested
+            // instantiations left to report success for in this test.
+            let snippet = result.unwrap();
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    __COMMENT__ _
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_unsupported_fn_async() {
+        // `async fn`s are still unsupported -- but (unlike a plain, non-`async`,
+        // non-`extern "C"` function; see `test_format_def_fn_non_c_abi_generates_thunk`)
+        // not because of the ABI mismatch.  The `async fn`'s return type desugars to an
+        // opaque `impl Future<...>`, and `format_ty` doesn't know how to represent that
+        // in C++ at all, so formatting the return type itself is what fails.
+        let test_src = r#"
+                pub async fn async_function() {}
+            "#;
+        test_format_def(test_src, "async_function", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert!(
+                err.starts_with("Error formatting function return type: "),
+                "unexpected error: {err}"
+            );
+        });
+    }
+
+    #[test]
+    fn test_format_def_fn_non_c_abi_generates_thunk() {
+        // A `pub fn` without an explicit ABI doesn't use the "C" ABI, so it can't be
+        // called directly from C++.  We should still generate bindings for it, via a
+        // `#[no_mangle] pub extern "C"` Rust thunk.
+        let test_src = r#"
+                pub fn add(a: i32, b: i32) -> i32 { a + b }
+            "#;
+        test_format_def(test_src, "add", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" std::int32_t __crubit_thunk_add(std::int32_t a, std::int32_t b);
+                    inline std::int32_t add(std::int32_t a, std::int32_t b) {
+                        return __crubit_thunk_add(a, b);
+                    }
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_add(a: i32, b: i32) -> i32 {
+                        add(a, b)
                     }
                 }
             );
@@ -447,277 +2391,661 @@ pub mod tests {
     }
 
     #[test]
-    fn test_generated_bindings_unsupported_item() {
-        // This test verifies how `Err` from `format_def` is formatted as a C++ comment
-        // (in `format_crate` and `format_unsupported_def`).
-        // - This test covers only a single example of an unsupported item.  Additional
-        //   coverage is provided by `test_format_def_unsupported_...` tests.
-        // - This test somewhat arbitrarily chooses an example of an unsupported item,
-        //   trying to pick one that 1) will never be supported (b/254104998 has some extra
-        //   notes about APIs named after reserved C++ keywords) and 2) tests that the
-        //   full error chain is included in the message.
+    fn test_format_def_fn_non_c_abi_void_return_generates_thunk() {
+        // Same as `test_format_def_fn_non_c_abi_generates_thunk` above, but covering the
+        // `()`-return-type case, where the C++ wrapper must not `return` the thunk's
+        // (`void`) result.
         let test_src = r#"
-                pub extern "C" fn reinterpret_cast() {}
+                pub fn default_rust_abi_function() {}
+            "#;
+        test_format_def(test_src, "default_rust_abi_function", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" void __crubit_thunk_default_rust_abi_function();
+                    inline void default_rust_abi_function() {
+                        __crubit_thunk_default_rust_abi_function();
+                    }
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn test_format_def_fn_non_c_abi_raw_pointer_generates_thunk() {
+        // The thunk's parameter and return types are formatted via the same
+        // `format_ty` as any other function (see `test_format_def_fn_non_c_abi_generates_thunk`
+        // above), so the non-"C"-ABI restriction is lifted for raw pointers just like it is
+        // for the scalar and reference cases covered elsewhere.
+        let test_src = r#"
+                pub fn first(s: *const i32) -> *mut i32 { s as *mut i32 }
+            "#;
+        test_format_def(test_src, "first", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" std::int32_t * __crubit_thunk_first(const std::int32_t * s);
+                    inline std::int32_t * first(const std::int32_t * s) {
+                        return __crubit_thunk_first(s);
+                    }
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_first(s: *const i32) -> *mut i32 {
+                        first(s)
+                    }
+                }
+            );
+        })
+    }
+
+    #[test]
+    fn test_format_def_unsupported_fn_variadic() {
+        let test_src = r#"
+                #![feature(c_variadic)]
+                pub unsafe extern "C" fn variadic_function(_fmt: *const u8, ...) {}
+            "#;
+        test_format_def(test_src, "variadic_function", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert_eq!(err, "C variadic functions are not supported (b/254097223)");
+        });
+    }
+
+    #[test]
+    fn test_format_def_fn_with_params() {
+        let test_src = r#"
+                pub extern "C" fn add(a: i32, b: i32) -> i32 { a + b }
             "#;
-        test_generated_bindings(test_src, |bindings| {
-            let bindings = bindings.expect("Test expects success");
-            let expected_comment_txt = "Error generating bindings for `reinterpret_cast` \
-                 defined at <crubit_unittests.rs>:2:17: 2:53: \
-                 Error formatting function name: \
-                 `reinterpret_cast` is a C++ reserved keyword \
-                 and can't be used as a C++ identifier";
+        test_format_def(test_src, "add", |result| {
             assert_cc_matches!(
-                bindings.h_body,
+                result.expect("Test expects success here").cc.tokens,
                 quote! {
-                    __COMMENT__ #expected_comment_txt
+                    extern "C" std::int32_t add(std::int32_t a, std::int32_t b);
                 }
             );
-        })
+        });
     }
 
     #[test]
-    fn test_format_def_fn_extern_c_no_params_no_return_type() {
+    fn test_format_target_feature_attr_no_features() {
         let test_src = r#"
-                pub extern "C" fn public_function() {}
+                pub fn foo() {}
             "#;
-        test_format_def(test_src, "public_function", |result| {
-            assert_cc_matches!(
-                result.expect("Test expects success here"),
-                quote! {
-                    extern "C" void public_function();
-                }
-            );
+        run_compiler(test_src, |tcx| {
+            let def_id = find_def_id_by_name(tcx, "foo");
+            let attr = format_target_feature_attr(tcx, def_id).unwrap();
+            assert!(attr.is_empty(), "expected no attribute, got: {attr}");
         });
     }
 
     #[test]
-    fn test_format_def_fn_extern_c_no_params_unit_return_type() {
-        // This test is very similar to the
-        // `test_format_def_fn_extern_c_no_params_no_return_type` above, except
-        // that the return type is explicitly spelled out.  There is no difference in
-        // `ty::FnSig` so our code behaves exactly the same, but the test has been
-        // planned based on earlier, hir-focused approach and having this extra
-        // test coverage shouldn't hurt. (`hir::FnSig` and `hir::FnRetTy` _do_
-        // see a difference between the two tests).
+    fn test_format_target_feature_attr_known_feature() {
         let test_src = r#"
-                pub extern "C" fn explicit_unit_return_type() -> () {}
+                #![allow(dead_code)]
+                #[target_feature(enable = "avx2")]
+                unsafe fn foo() {}
             "#;
-        test_format_def(test_src, "explicit_unit_return_type", |result| {
-            assert_cc_matches!(
-                result.expect("Test expects success here"),
-                quote! {
-                    extern "C" void explicit_unit_return_type();
-                }
+        run_compiler(test_src, |tcx| {
+            let def_id = find_def_id_by_name(tcx, "foo");
+            let attr = format_target_feature_attr(tcx, def_id).unwrap();
+            assert_cc_matches!(attr, quote! { __attribute__((target("avx2"))) });
+        });
+    }
+
+    #[test]
+    fn test_format_target_feature_attr_unknown_feature_is_rejected() {
+        // `sse4a` is a real (stable) x86 target feature, but Crubit doesn't yet know
+        // its Clang/GCC spelling, so it should be rejected rather than silently
+        // dropped.
+        let test_src = r#"
+                #![allow(dead_code)]
+                #[target_feature(enable = "sse4a")]
+                unsafe fn foo() {}
+            "#;
+        run_compiler(test_src, |tcx| {
+            let def_id = find_def_id_by_name(tcx, "foo");
+            let err = format_target_feature_attr(tcx, def_id).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Rust target feature `sse4a` has no known C++ equivalent (b/254096564)"
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_unsafe() {
-        // This tests how bindings for an `unsafe fn` are generated.
+    fn test_format_def_unsupported_hir_item_kind() {
         let test_src = r#"
-                pub unsafe extern "C" fn foo() {}
+                pub const SOME_CONST: i32 = 42;
             "#;
-        test_format_def(test_src, "foo", |result| {
+        test_format_def(test_src, "SOME_CONST", |result| {
+            let err = result.expect_err("Test expects an error here");
+            assert_eq!(err, "Unsupported rustc_hir::hir::ItemKind: constant item");
+        });
+    }
+
+    #[test]
+    fn test_format_def_struct_without_repr_c_is_unsupported() {
+        // Rust's default struct layout is unspecified, so without `#[repr(C)]` (or
+        // `#[repr(transparent)]`) there's no layout we could safely reproduce in C++.
+        let test_src = r#"
+                pub struct SomeStruct(i32);
+            "#;
+        test_format_def(test_src, "SomeStruct", |result| {
             let err = result.expect_err("Test expects an error here");
             assert_eq!(
                 err,
-                "Bindings for `unsafe` functions \
-                             are not fully designed yet (b/254095482)"
+                "Only structs annotated with `#[repr(C)]` or `#[repr(transparent)]` can be \
+                 translated to a C++ struct, because Rust's default layout is unspecified"
             );
         });
     }
 
     #[test]
-    fn test_format_def_fn_const() {
-        // This tests how bindings for an `const fn` are generated.
-        //
-        // Right now the `const` qualifier is ignored, but one can imagine that in the
-        // (very) long-term future such functions (including their bodies) could
-        // be translated into C++ `consteval` functions.
+    fn test_format_def_generic_struct_is_unsupported() {
         let test_src = r#"
-                pub const fn foo(i: i32) -> i32 { i * 42 }
+                #[repr(C)]
+                pub struct Pair<T> {
+                    pub first: T,
+                    pub second: T,
+                }
             "#;
-        test_format_def(test_src, "foo", |result| {
-            // TODO(lukasza): Update test expectations below once `const fn` example from
-            // the testcase doesn't just error out (and is instead supported as
-            // a non-`consteval` binding).
-            // TODO(b/254095787): Update test expectations below once `const fn` from Rust
-            // is translated into a `consteval` C++ function.
+        test_format_def(test_src, "Pair", |result| {
             let err = result.expect_err("Test expects an error here");
-            assert_eq!(err, "Function parameters are not supported yet",);
+            assert_eq!(err, "Generic structs are not supported yet (b/254099023)");
         });
     }
 
     #[test]
-    fn test_format_def_fn_with_c_unwind_abi() {
-        // See also https://rust-lang.github.io/rfcs/2945-c-unwind-abi.html
+    fn test_format_def_repr_c_struct() {
         let test_src = r#"
-                #![feature(c_unwind)]
-                pub extern "C-unwind" fn may_throw() {}
+                #[repr(C)]
+                pub struct Point {
+                    pub x: i32,
+                    pub y: i32,
+                }
             "#;
-        test_format_def(test_src, "may_throw", |result| {
+        test_format_def(test_src, "Point", |result| {
+            let snippet = result.expect("Test expects success here");
             assert_cc_matches!(
-                result.expect("Test expects success here"),
+                snippet.cc.tokens,
                 quote! {
-                    extern "C" void may_throw();
+                    struct Point {
+                        std::int32_t x;
+                        std::int32_t y;
+                    };
                 }
             );
+            let includes: Vec<&str> = snippet.cc.includes.into_iter().collect();
+            assert_eq!(includes, vec!["cstdint"]);
         });
     }
 
     #[test]
-    fn test_format_def_fn_with_type_aliased_return_type() {
-        // Type aliases disappear at the `rustc_middle::ty::Ty` level and therefore in
-        // the short-term the generated bindings also ignore type aliases.
-        //
-        // TODO(b/254096006): Consider preserving `type` aliases when generating
-        // bindings.
+    fn test_format_def_repr_c_struct_not_unpin_emits_emplacement_thunk() {
+        // The explicit `impl !Unpin` makes the ordinary construct-then-move-into-`__ret_slot`
+        // thunk pattern unsound for `PinnedPoint` (the move could invalidate self-references
+        // a real `!Unpin` type might hold), so it should get an in-place emplacement thunk
+        // instead.
         let test_src = r#"
-                type MyTypeAlias = f64;
-
-                pub extern "C" fn type_aliased_return() -> MyTypeAlias { 42.0 }
+                #![feature(negative_impls)]
+                #[repr(C)]
+                #[derive(Default)]
+                pub struct PinnedPoint {
+                    pub x: i32,
+                    pub y: i32,
+                }
+                impl !Unpin for PinnedPoint {}
             "#;
-        test_format_def(test_src, "type_aliased_return", |result| {
+        test_format_def(test_src, "PinnedPoint", |result| {
+            let snippet = result.expect("Test expects success here");
             assert_cc_matches!(
-                result.expect("Test expects success here"),
+                snippet.cc.tokens,
                 quote! {
-                    extern "C" double type_aliased_return();
+                    struct PinnedPoint {
+                        std::int32_t x;
+                        std::int32_t y;
+                        PinnedPoint() {
+                            if (!__crubit_thunk_emplace_default_PinnedPoint(this)) {
+                                std::abort();
+                            }
+                        }
+                    };
+                    extern "C" bool __crubit_thunk_emplace_default_PinnedPoint(PinnedPoint* __ret_slot);
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_emplace_default_PinnedPoint(
+                        __ret_slot: *mut PinnedPoint,
+                    ) -> bool {
+                        unsafe {
+                            let x = match ::std::panic::catch_unwind(
+                                ::std::panic::AssertUnwindSafe(|| <i32 as ::std::default::Default>::default()),
+                            ) {
+                                ::std::result::Result::Ok(value) => value,
+                                ::std::result::Result::Err(_) => {
+                                    return false;
+                                }
+                            };
+                            ::std::ptr::addr_of_mut!((*__ret_slot).x).write(x);
+                            let y = match ::std::panic::catch_unwind(
+                                ::std::panic::AssertUnwindSafe(|| <i32 as ::std::default::Default>::default()),
+                            ) {
+                                ::std::result::Result::Ok(value) => value,
+                                ::std::result::Result::Err(_) => {
+                                    ::std::ptr::drop_in_place(::std::ptr::addr_of_mut!((*__ret_slot).x));
+                                    return false;
+                                }
+                            };
+                            ::std::ptr::addr_of_mut!((*__ret_slot).y).write(y);
+                            true
+                        }
+                    }
                 }
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_name_is_reserved_cpp_keyword() {
+    fn test_format_def_struct_with_drop_emits_unwind_safe_drop_thunk() {
         let test_src = r#"
-                pub extern "C" fn reinterpret_cast() -> () {}
+                #[repr(C)]
+                pub struct HasDrop {
+                    pub x: i32,
+                }
+                impl Drop for HasDrop {
+                    fn drop(&mut self) {}
+                }
             "#;
-        test_format_def(test_src, "reinterpret_cast", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(
-                err,
-                "Error formatting function name: \
-                       `reinterpret_cast` is a C++ reserved keyword \
-                       and can't be used as a C++ identifier"
+        test_format_def(test_src, "HasDrop", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    struct HasDrop {
+                        std::int32_t x;
+                        ~HasDrop() { __crubit_thunk_drop_HasDrop(this); }
+                    };
+                    extern "C" void __crubit_thunk_drop_HasDrop(HasDrop* self_);
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_drop_HasDrop(self_: *mut HasDrop) {
+                        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| unsafe {
+                            ::std::ptr::drop_in_place(self_);
+                        }));
+                        if result.is_err() {
+                            ::std::eprintln!(
+                                "Rust panicked in `{}`; aborting rather than unwinding across the C++ FFI boundary",
+                                "__crubit_thunk_drop_HasDrop",
+                            );
+                            ::std::process::abort();
+                        }
+                    }
+                }
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_ret_type() {
+    fn test_format_def_struct_without_drop_has_no_drop_thunk() {
         let test_src = r#"
-                pub extern "C" fn foo() -> *const i32 { std::ptr::null() }
+                #[repr(C)]
+                pub struct Plain {
+                    pub x: i32,
+                }
             "#;
-        test_format_def(test_src, "foo", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(
-                err,
-                "Error formatting function return type: \
-                       The following Rust type is not supported yet: *const i32"
+        test_format_def(test_src, "Plain", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert!(!snippet.cc.tokens.to_string().contains("__crubit_thunk_drop"));
+            // `x` is `pub`, so it gets an offset assertion (emitted for every reachable
+            // field) but no accessor thunk (already a directly visible C++ struct member).
+            assert!(!snippet.rs_thunks.is_empty());
+            assert!(!snippet.rs_thunks.to_string().contains("__crubit_thunk_field_ptr"));
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Plain, x) == 0u64); }
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_with_late_bound_lifetimes() {
+    fn test_format_def_struct_with_clone_emits_unwind_safe_clone_thunk() {
         let test_src = r#"
-                pub fn foo(arg: &i32) -> &i32 { arg }
-
-                // Lifetime inference translates the above into:
-                //     pub fn foo<'a>(arg: &'a i32) -> &'a i32 { ... }
-                // leaving 'a lifetime late-bound (it is bound with a lifetime
-                // taken from each of the callsites).  In other words, we can't
-                // just call `no_bound_vars` on this `FnSig`'s `Binder`.
+                #[repr(C)]
+                #[derive(Clone)]
+                pub struct HasClone {
+                    pub x: i32,
+                }
             "#;
-        test_format_def(test_src, "foo", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(
-                err,
-                "Generic functions (lifetime-generic or type-generic) are not supported yet"
+        test_format_def(test_src, "HasClone", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    struct HasClone {
+                        std::int32_t x;
+                        HasClone(HasClone const& other) { __crubit_thunk_clone_HasClone(&other, this); }
+                        HasClone& operator=(HasClone const& other) {
+                            if (this != &other) {
+                                this->~HasClone();
+                                ::new (this) HasClone(other);
+                            }
+                            return *this;
+                        }
+                    };
+                    extern "C" bool __crubit_thunk_clone_HasClone(HasClone const* self_, HasClone* __ret_slot);
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_clone_HasClone(
+                        self_: *const HasClone,
+                        __ret_slot: *mut HasClone,
+                    ) -> bool {
+                        let __ret_slot = unsafe { __crubit::OutRef::new(__ret_slot) };
+                        match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| unsafe {
+                            ::std::clone::Clone::clone(&*self_)
+                        })) {
+                            ::std::result::Result::Ok(value) => {
+                                __ret_slot.write(value);
+                                true
+                            }
+                            ::std::result::Result::Err(_) => {
+                                ::std::eprintln!(
+                                    "Rust panicked in `{}`; aborting rather than unwinding across the C++ FFI boundary",
+                                    "__crubit_thunk_clone_HasClone",
+                                );
+                                ::std::process::abort();
+                            }
+                        }
+                    }
+                }
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_generic_fn() {
+    fn test_format_def_derived_clone_has_no_clone_from_thunk() {
         let test_src = r#"
-                use std::default::Default;
-                use std::fmt::Display;
-                pub fn generic_function<T: Default + Display>() {
-                    println!("{}", T::default());
+                #[repr(C)]
+                #[derive(Clone)]
+                pub struct HasClone {
+                    pub x: i32,
                 }
             "#;
-        test_format_def(test_src, "generic_function", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(
-                err,
-                "Generic functions (lifetime-generic or type-generic) are not supported yet"
+        test_format_def(test_src, "HasClone", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert!(!snippet.cc.tokens.to_string().contains("__crubit_thunk_clone_from"));
+            assert!(!snippet.rs_thunks.to_string().contains("__crubit_thunk_clone_from"));
+        });
+    }
+
+    #[test]
+    fn test_format_def_struct_with_custom_clone_from_emits_clone_from_thunk() {
+        let test_src = r#"
+                #[repr(C)]
+                pub struct HasCustomCloneFrom {
+                    pub x: i32,
+                }
+                impl Clone for HasCustomCloneFrom {
+                    fn clone(&self) -> Self {
+                        HasCustomCloneFrom { x: self.x }
+                    }
+                    fn clone_from(&mut self, source: &Self) {
+                        self.x = source.x;
+                    }
+                }
+            "#;
+        test_format_def(test_src, "HasCustomCloneFrom", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    struct HasCustomCloneFrom {
+                        std::int32_t x;
+                        HasCustomCloneFrom(HasCustomCloneFrom const& other) {
+                            __crubit_thunk_clone_HasCustomCloneFrom(&other, this);
+                        }
+                        HasCustomCloneFrom& operator=(HasCustomCloneFrom const& other) {
+                            if (this != &other) { __crubit_thunk_clone_from_HasCustomCloneFrom(this, &other); }
+                            return *this;
+                        }
+                    };
+                    extern "C" void __crubit_thunk_clone_from_HasCustomCloneFrom(
+                        HasCustomCloneFrom* self_,
+                        HasCustomCloneFrom const* source
+                    );
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_clone_from_HasCustomCloneFrom(
+                        self_: *mut HasCustomCloneFrom,
+                        source: *const HasCustomCloneFrom,
+                    ) {
+                        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| unsafe {
+                            ::std::clone::Clone::clone_from(&mut *self_, &*source)
+                        }));
+                        if result.is_err() {
+                            ::std::eprintln!(
+                                "Rust panicked in `{}`; aborting rather than unwinding across the C++ FFI boundary",
+                                "__crubit_thunk_clone_from_HasCustomCloneFrom",
+                            );
+                            ::std::process::abort();
+                        }
+                    }
+                }
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_async() {
+    fn test_format_def_struct_offsets_and_private_field_accessors() {
         let test_src = r#"
-                pub async fn async_function() {}
+                #![allow(dead_code)]
+                #[repr(C)]
+                pub struct Inner {
+                    pub a: i32,
+                    b: i32,
+                }
+                #[repr(C)]
+                pub struct Outer {
+                    pub x: i32,
+                    inner: Inner,
+                }
             "#;
-        test_format_def(test_src, "async_function", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(
-                err,
-                "Functions that require Rust thunks (e.g. non-`extern \"C\"`) \
-                 are not supported yet (b/254097223)"
+        test_format_def(test_src, "Outer", |result| {
+            let snippet = result.expect("Test expects success here");
+
+            // Every reachable field -- including ones nested inside `Inner` -- gets an
+            // absolute-offset assertion, regardless of visibility.
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Outer, x) == 0u64); }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Outer, inner) == 4u64); }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Outer, inner.a) == 4u64); }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Outer, inner.b) == 8u64); }
+            );
+
+            // `x` and `inner.a` are `pub`, so they're already directly accessible as members
+            // of the mirrored C++ struct and get no accessor thunk; `inner` and `inner.b`
+            // aren't, so they do.
+            assert_cc_not_matches!(snippet.cc.tokens, quote! { __crubit_thunk_field_ptr_x });
+            assert_cc_not_matches!(snippet.cc.tokens, quote! { __crubit_thunk_field_ptr_inner_a });
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" Inner* __crubit_thunk_field_ptr_inner(Outer* self_);
+                    extern "C" Inner const* __crubit_thunk_field_ptr_const_inner(Outer const* self_);
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_field_ptr_inner_b(self_: *mut Outer) -> *mut i32 {
+                        unsafe { ::std::ptr::addr_of_mut!((*self_).inner.b) }
+                    }
+                }
             );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_non_c_abi() {
+    fn test_format_def_tuple_struct_has_renamed_fields_and_indexed_offsets() {
+        // Regression test: a tuple struct's `FieldDef::name`s are bare decimal indices
+        // ("0", "1", ...), which aren't valid C++ identifiers and would panic if fed
+        // straight to `format_rs_ident`/`format_cc_ident` -- this must neither crash nor
+        // silently drop the tuple fields.
         let test_src = r#"
-                pub fn default_rust_abi_function() {}
+                #[repr(C)]
+                pub struct Pair(pub u8, u16);
             "#;
-        test_format_def(test_src, "default_rust_abi_function", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(
-                err,
-                "Functions that require Rust thunks \
-                       (e.g. non-`extern \"C\"`) are not supported yet (b/254097223)"
+        test_format_def(test_src, "Pair", |result| {
+            let snippet = result.expect("Test expects success here");
+            // The mirrored C++ struct can't use bare numeric member names, so tuple fields
+            // get an `_`-prefixed one.
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    struct Pair {
+                        std::uint8_t _0;
+                        std::uint16_t _1;
+                    };
+                }
             );
-        })
+            // The offset assertions address the real Rust field by its numeric index, which
+            // `offset_of!` and ordinary field access both accept.
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Pair, 0) == 0u64); }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! { const _: () = assert!(::core::mem::offset_of!(Pair, 1) == 2u64); }
+            );
+            // Field `0` is `pub`, so it's directly visible as `_0` and gets no accessor
+            // thunk; field `1` isn't `pub`, so it gets one, named after its numeric index.
+            assert_cc_not_matches!(snippet.cc.tokens, quote! { __crubit_thunk_field_ptr_0 });
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" std::uint16_t* __crubit_thunk_field_ptr_1(Pair* self_);
+                    extern "C" std::uint16_t const* __crubit_thunk_field_ptr_const_1(Pair const* self_);
+                }
+            );
+            assert_rs_matches!(
+                snippet.rs_thunks,
+                quote! {
+                    #[no_mangle]
+                    pub extern "C" fn __crubit_thunk_field_ptr_1(self_: *mut Pair) -> *mut u16 {
+                        unsafe { ::std::ptr::addr_of_mut!((*self_).1) }
+                    }
+                }
+            );
+        });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_variadic() {
+    fn test_format_def_struct_with_mixed_visibility_fields_has_single_access_block() {
+        // Regression test: a mix of `pub` and non-`pub` fields must not cause `format_adt`
+        // to emit `private:`/`public:` specifiers into the mirrored C++ struct, since the
+        // standard only guarantees relative field order *within* one access-specifier
+        // block, not across blocks -- the struct's fields must all land in the same block,
+        // in Rust declaration order, so `self_->field` keeps matching the real offset.
         let test_src = r#"
-                #![feature(c_variadic)]
-                pub unsafe extern "C" fn variadic_function(_fmt: *const u8, ...) {}
+                #![allow(dead_code)]
+                #[repr(C)]
+                pub struct MixedVisibility {
+                    pub a: i32,
+                    b: i32,
+                    pub c: i32,
+                }
             "#;
-        test_format_def(test_src, "variadic_function", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(err, "C variadic functions are not supported (b/254097223)");
+        test_format_def(test_src, "MixedVisibility", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_not_matches!(snippet.cc.tokens, quote! { private: });
+            assert_cc_not_matches!(snippet.cc.tokens, quote! { public: });
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    struct MixedVisibility {
+                        std::int32_t a;
+                        std::int32_t b;
+                        std::int32_t c;
+                    };
+                }
+            );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_fn_params() {
+    fn test_format_ty_repr_c_struct_by_name() {
+        // Referencing a `#[repr(C)]` struct by name in a function signature is a valid
+        // `format_ty` result (it's just the C++ struct's name -- the struct definition
+        // itself comes from `format_def`/`format_adt`).
         let test_src = r#"
-                pub unsafe extern "C" fn fn_with_params(_i: i32) {}
+                #[repr(C)]
+                pub struct Point {
+                    pub x: i32,
+                    pub y: i32,
+                }
+
+                pub extern "C" fn get_origin() -> Point { Point { x: 0, y: 0 } }
             "#;
-        test_format_def(test_src, "fn_with_params", |result| {
-            let err = result.expect_err("Test expects an error here");
-            assert_eq!(err, "Function parameters are not supported yet");
+        test_format_def(test_src, "get_origin", |result| {
+            let snippet = result.expect("Test expects success here");
+            assert_cc_matches!(
+                snippet.cc.tokens,
+                quote! {
+                    extern "C" Point get_origin();
+                }
+            );
         });
     }
 
     #[test]
-    fn test_format_def_unsupported_hir_item_kind() {
+    fn test_format_ty_non_repr_c_struct_by_name_is_unsupported() {
         let test_src = r#"
-                pub struct SomeStruct(i32);
+                pub struct Point {
+                    pub x: i32,
+                    pub y: i32,
+                }
+
+                pub extern "C" fn get_origin() -> Point { Point { x: 0, y: 0 } }
             "#;
-        test_format_def(test_src, "SomeStruct", |result| {
+        test_format_def(test_src, "get_origin", |result| {
             let err = result.expect_err("Test expects an error here");
-            assert_eq!(err, "Unsupported rustc_hir::hir::ItemKind: struct");
+            assert_eq!(
+                err,
+                "Error formatting function return type: \
+                 Only `#[repr(C)]` or `#[repr(transparent)]` structs can be used in a \
+                 public API, because Rust's default layout is unspecified: Point"
+            );
         });
     }
 
@@ -725,19 +3053,75 @@ pub mod tests {
     fn test_format_ty_successes() {
         // Test coverage for cases where `format_ty` returns an `Ok(...)`.
         let testcases = [
-            // ( <Rust type>, <expected C++ type> )
-            ("bool", "bool"),  // TyKind::Bool
-            ("f32", "float"),  // TyKind::Float(ty::FloatTy::F32)
-            ("f64", "double"), // TyKind::Float(ty::FloatTy::F64)
+            // ( <Rust type>, (<expected C++ type>, <expected #includes>) )
+            ("bool", ("bool", vec![])),      // TyKind::Bool
+            ("f32", ("float", vec![])),      // TyKind::Float(ty::FloatTy::F32)
+            ("f64", ("double", vec![])),     // TyKind::Float(ty::FloatTy::F64)
+            ("char", ("char32_t", vec![])),  // TyKind::Char
             // The unit type is a special (zero-length) kind of TyKind::Tuple
-            ("()", "void"),
+            ("()", ("void", vec![])),
             // Extra parens/sugar are expected to be ignored:
-            ("(bool)", "bool"),
+            ("(bool)", ("bool", vec![])),
+            ("i8", ("std::int8_t", vec!["cstdint"])),
+            ("i16", ("std::int16_t", vec!["cstdint"])),
+            ("i32", ("std::int32_t", vec!["cstdint"])),
+            ("i64", ("std::int64_t", vec!["cstdint"])),
+            ("isize", ("std::intptr_t", vec!["cstdint"])),
+            ("u8", ("std::uint8_t", vec!["cstdint"])),
+            ("u16", ("std::uint16_t", vec!["cstdint"])),
+            ("u32", ("std::uint32_t", vec!["cstdint"])),
+            ("u64", ("std::uint64_t", vec!["cstdint"])),
+            ("usize", ("std::uintptr_t", vec!["cstdint"])),
+            // TyKind::RawPtr -- constness comes from the pointee (`ty::TypeAndMut`).
+            ("*const i32", ("const std::int32_t *", vec!["cstdint"])),
+            ("*mut i32", ("std::int32_t *", vec!["cstdint"])),
+            // TyKind::Ref -- same constness rule, driven by `&`/`&mut`.
+            ("&'static i32", ("const std::int32_t &", vec!["cstdint"])),
+            ("&'static mut i32", ("std::int32_t &", vec!["cstdint"])),
+        ];
+        test_format_ty(&testcases, |desc, tcx, ty, (expected, expected_includes)| {
+            let cc_snippet = format_ty(tcx, ty, &BindingsConfig::new()).unwrap();
+            let actual = cc_snippet.tokens.to_string();
+            let expected = expected.parse::<TokenStream>().unwrap().to_string();
+            assert_eq!(actual, expected, "{desc}");
+            let actual_includes: Vec<&str> = cc_snippet.includes.into_iter().collect();
+            assert_eq!(&actual_includes, expected_includes, "{desc}");
+        });
+    }
+
+    #[test]
+    fn test_format_ty_i128_u128_clang_builtin_backend() {
+        let mut config = BindingsConfig::new();
+        config.set_int128_backend(Int128Backend::ClangBuiltin);
+        let testcases = [
+            ("i128", ("__int128", vec![])),
+            ("u128", ("unsigned __int128", vec![])),
+        ];
+        test_format_ty(&testcases, |desc, tcx, ty, (expected, expected_includes)| {
+            let cc_snippet = format_ty(tcx, ty, &config).unwrap();
+            let actual = cc_snippet.tokens.to_string();
+            let expected = expected.parse::<TokenStream>().unwrap().to_string();
+            assert_eq!(actual, expected, "{desc}");
+            let actual_includes: Vec<&str> = cc_snippet.includes.into_iter().collect();
+            assert_eq!(&actual_includes, expected_includes, "{desc}");
+        });
+    }
+
+    #[test]
+    fn test_format_ty_i128_u128_abseil_backend() {
+        let mut config = BindingsConfig::new();
+        config.set_int128_backend(Int128Backend::Abseil);
+        let testcases = [
+            ("i128", ("absl::int128", vec!["absl/numeric/int128.h"])),
+            ("u128", ("absl::uint128", vec!["absl/numeric/int128.h"])),
         ];
-        test_format_ty(&testcases, |desc, ty, expected| {
-            let actual = format_ty(ty).unwrap().to_string();
+        test_format_ty(&testcases, |desc, tcx, ty, (expected, expected_includes)| {
+            let cc_snippet = format_ty(tcx, ty, &config).unwrap();
+            let actual = cc_snippet.tokens.to_string();
             let expected = expected.parse::<TokenStream>().unwrap().to_string();
             assert_eq!(actual, expected, "{desc}");
+            let actual_includes: Vec<&str> = cc_snippet.includes.into_iter().collect();
+            assert_eq!(&actual_includes, expected_includes, "{desc}");
         });
     }
 
@@ -771,37 +3155,24 @@ pub mod tests {
                 "(i32, i32)", // TyKind::Tuple
                 "Tuples are not supported yet: (i32, i32) (b/254097223)",
             ),
-            (
-                "char", // TyKind::Char
-                "No support yet for `#include`ing C++ equivalent of `char` (b/254094545)",
-            ),
-            (
-                "i32", // TyKind::Int
-                "No support yet for `#include`ing C++ equivalent of `i32` (b/254094545)",
-            ),
-            (
-                "u32", // TyKind::UInt
-                "No support yet for `#include`ing C++ equivalent of `u32` (b/254094545)",
-            ),
-            (
-                "*const i32", // TyKind::Ptr
-                "The following Rust type is not supported yet: *const i32",
-            ),
-            (
-                "&'static i32", // TyKind::Ref
-                "The following Rust type is not supported yet: &'static i32",
-            ),
             (
                 "[i32; 42]", // TyKind::Array
                 "The following Rust type is not supported yet: [i32; 42]",
             ),
             (
                 "&'static [i32]", // TyKind::Slice (nested underneath TyKind::Ref)
-                "The following Rust type is not supported yet: &'static [i32]",
+                "Formatting a reference to slice/`str` is not supported yet: \
+                 [i32] needs a fat-pointer ABI",
             ),
             (
                 "&'static str", // TyKind::Str (nested underneath TyKind::Ref)
-                "The following Rust type is not supported yet: &'static str",
+                "Formatting a reference to slice/`str` is not supported yet: \
+                 str needs a fat-pointer ABI",
+            ),
+            (
+                "*const [i32]", // TyKind::Slice (nested underneath TyKind::RawPtr)
+                "Formatting a pointer to slice/`str` is not supported yet: \
+                 [i32] needs a fat-pointer ABI",
             ),
             (
                 "impl Eq", // TyKind::Opaque
@@ -816,8 +3187,8 @@ pub mod tests {
             ("i128", "C++ doesn't have a standard equivalent of `i128` (b/254094650)"),
             ("u128", "C++ doesn't have a standard equivalent of `u128` (b/254094650)"),
         ];
-        test_format_ty(&testcases, |desc, ty, expected_err| {
-            let anyhow_err = format_ty(ty).unwrap_err();
+        test_format_ty(&testcases, |desc, tcx, ty, expected_err| {
+            let anyhow_err = format_ty(tcx, ty, &BindingsConfig::new()).unwrap_err();
             let actual_err = format!("{anyhow_err:#}");
             assert_eq!(&actual_err, *expected_err, "{desc}");
         });
@@ -825,7 +3196,7 @@ pub mod tests {
 
     fn test_format_ty<TestFn, Expectation>(testcases: &[(&str, Expectation)], test_fn: TestFn)
     where
-        TestFn: Fn(/* testcase_description: */ &str, Ty, &Expectation) -> () + Sync,
+        TestFn: Fn(/* testcase_description: */ &str, TyCtxt, Ty, &Expectation) -> () + Sync,
         Expectation: Sync,
     {
         for (index, (input, expected)) in testcases.into_iter().enumerate() {
@@ -841,7 +3212,7 @@ pub mod tests {
             run_compiler(input, |tcx| {
                 let def_id = find_def_id_by_name(tcx, "test_function");
                 let ty = tcx.fn_sig(def_id.to_def_id()).no_bound_vars().unwrap().output();
-                test_fn(&desc, ty, expected);
+                test_fn(&desc, tcx, ty, expected);
             });
         }
     }
@@ -853,12 +3224,27 @@ pub mod tests {
     /// result from `format_def`.)
     fn test_format_def<F, T>(source: &str, name: &str, test_function: F) -> T
     where
-        F: FnOnce(Result<TokenStream, String>) -> T + Send,
+        F: FnOnce(Result<ItemSnippet, String>) -> T + Send,
+        T: Send,
+    {
+        test_format_def_with_config(source, name, &BindingsConfig::new(), test_function)
+    }
+
+    /// Like `test_format_def`, but also passes `config` through to `format_def`, for tests
+    /// that need to configure the set of generic-function instantiations to generate.
+    fn test_format_def_with_config<F, T>(
+        source: &str,
+        name: &str,
+        config: &BindingsConfig,
+        test_function: F,
+    ) -> T
+    where
+        F: FnOnce(Result<ItemSnippet, String>) -> T + Send,
         T: Send,
     {
         run_compiler(source, |tcx| {
             let def_id = find_def_id_by_name(tcx, name);
-            let result = format_def(tcx, def_id);
+            let result = format_def(tcx, def_id, config);
 
             // https://docs.rs/anyhow/latest/anyhow/struct.Error.html#display-representations says:
             // To print causes as well [...], use the alternate selector “{:#}”.
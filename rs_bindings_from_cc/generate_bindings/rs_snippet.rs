@@ -14,6 +14,8 @@ use ir::*;
 use itertools::Itertools;
 use proc_macro2::{Ident, TokenStream};
 use quote::{quote, ToTokens};
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::rc::Rc;
 use std::sync::LazyLock;
@@ -21,18 +23,42 @@ use token_stream_printer::write_unformatted_tokens;
 
 const SLICE_REF_NAME_RS: &str = "&[]";
 
+/// A fully-qualified Rust item path that the crate-level generator should
+/// emit as a `use` item (e.g. `UsePath::new("core::pin::Pin")` for
+/// `use ::core::pin::Pin;`), so that `tokens` can refer to the item
+/// unqualified. Ordered by path, so a `BTreeSet<UsePath>` yields a
+/// deterministic, sorted `use` block.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UsePath(pub Rc<str>);
+
+impl UsePath {
+    pub fn new(path: &str) -> Self {
+        UsePath(Rc::from(path))
+    }
+}
+
+impl ToTokens for UsePath {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let path: TokenStream = self.0.parse().expect("Invalid UsePath");
+        quote! { use :: #path; }.to_tokens(tokens);
+    }
+}
+
 /// A struct with information associated with the formatted Rust code snippet.
 #[derive(Clone, Debug)]
 pub struct RsSnippet {
     pub tokens: TokenStream,
     // The Rust features that are needed for `tokens` to work.
     pub features: HashSet<Ident>,
+    // The `use` items that `tokens` relies on being in scope (see `UsePath`).
+    pub imports: BTreeSet<UsePath>,
 }
 
 impl RsSnippet {
-    /// Convenience function to initialize RsSnippet with empty `features`.
+    /// Convenience function to initialize RsSnippet with empty `features` and
+    /// `imports`.
     pub fn new(tokens: TokenStream) -> RsSnippet {
-        RsSnippet { tokens, features: HashSet::<Ident>::new() }
+        RsSnippet { tokens, features: HashSet::<Ident>::new(), imports: BTreeSet::new() }
     }
 }
 
@@ -58,6 +84,33 @@ impl Mutability {
     }
 }
 
+/// The ABI strategy for passing a value across the `extern "C"` boundary, returned by
+/// `RsTypeKind::by_value_strategy`.
+///
+/// NOTE: call-site codegen doesn't read this yet -- `by_value_strategy`/
+/// `is_c_abi_compatible_by_value` are classification helpers only, not wired into function
+/// signature generation. A record that resolves to `Thunk` is today handled the same as any
+/// other non-`Direct` case: `check_by_value`/`as_opaque_by_value` (see those doc comments)
+/// decide whether it gets bound at all, not this enum. `Thunk` is reserved for a future pass
+/// that would generate pointer-slot-based C++ thunks for by-value signatures instead of
+/// rejecting or opaque-handling them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ByValueStrategy {
+    /// The value's Rust layout is a faithful copy of its C++ layout, so it
+    /// can be passed directly as an `extern "C"` by-value parameter or
+    /// return type.
+    Direct,
+    /// The value can't be passed directly (e.g. a non-trivially-movable
+    /// record, or one whose generated layout isn't a faithful replica of its
+    /// C++ layout). Passing it by value would instead require a C++-side
+    /// thunk that constructs (for a return value) or reads (for a parameter)
+    /// the value in place through a caller-allocated pointer slot, the same
+    /// way bindgen's `serialize` module falls back to wrapper functions for
+    /// types that can't cross the boundary directly -- but no such thunk is
+    /// generated yet (see the note above).
+    Thunk,
+}
+
 /// Either a named lifetime, or the magic `'_` elided lifetime.
 ///
 /// Warning: elided lifetimes are not always valid, and sometimes named
@@ -158,6 +211,19 @@ pub fn format_generic_params_replacing_by_self<'a>(
     )
 }
 
+/// Formats a `for<'a, 'b>` higher-ranked lifetime binder, or nothing if
+/// `bound_lifetimes` is empty.
+pub fn format_lifetime_binder<'a>(
+    bound_lifetimes: impl IntoIterator<Item = &'a Lifetime>,
+) -> TokenStream {
+    let mut bound_lifetimes = bound_lifetimes.into_iter().peekable();
+    if bound_lifetimes.peek().is_none() {
+        quote! {}
+    } else {
+        quote! { for < #(#bound_lifetimes),* > }
+    }
+}
+
 // TODO(jeanpierreda): These functions are at a weird level of abstraction (using
 // ir::Record). It's possible that, instead, we should just ask "does the
 // RsTypeKind implement clone" (etc.).
@@ -199,6 +265,30 @@ pub fn check_by_value(record: &Record) -> Result<()> {
     Ok(())
 }
 
+/// Returns true if `field`'s C++ type is known, from its raw IR type alone,
+/// to be represented by Rust with a faithful, byte-for-byte identical
+/// layout -- a primitive scalar or a raw pointer. Neither of those is ever
+/// substituted by `rs_bindings_from_cc` with an opaque blob or reshaped, the
+/// way it does e.g. for a field of unsupported type, or for a
+/// `no_unique_address` member.
+///
+/// A nested record/enum/type-alias field (`rs_type.name.is_none()`) can't be
+/// verified this way: this function has no access to `IR`, so it can't
+/// recurse into the referenced item to check whether it was itself reshaped.
+/// Such fields are conservatively treated as unverified.
+fn field_is_verified_faithful_copy(field: &Field) -> bool {
+    match field.type_.rs_type.name.as_deref() {
+        Some("*mut") | Some("*const") => true,
+        // Covers both the fixed-width Rust names (`i32`, `f64`, ...) and the
+        // `::core::ffi::c_*` names that C++ builtin integer types (e.g. `int`) map to.
+        Some(name) => matches!(
+            PrimitiveType::from_str(name),
+            Some(primitive) if primitive != PrimitiveType::Unit
+        ),
+        None => false,
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PrimitiveType {
@@ -213,10 +303,20 @@ pub enum PrimitiveType {
     i32,
     u64,
     i64,
+    u128,
+    i128,
     usize,
     isize,
     f32,
     f64,
+    /// C++ `char8_t`, represented as `u8` since Rust has no stable `char8_t`.
+    char8_t,
+    /// C++ `char16_t`, represented as `u16` since Rust has no stable
+    /// `char16_t`.
+    char16_t,
+    /// C++ `char32_t`, represented as `u32` rather than `char`, since not
+    /// every `char32_t` value is a valid Unicode scalar value.
+    char32_t,
     c_char,
     c_uchar,
     c_schar,
@@ -243,10 +343,15 @@ impl PrimitiveType {
             "i32" => Self::i32,
             "u64" => Self::u64,
             "i64" => Self::i64,
+            "u128" => Self::u128,
+            "i128" => Self::i128,
             "usize" => Self::usize,
             "isize" => Self::isize,
             "f32" => Self::f32,
             "f64" => Self::f64,
+            "char8_t" => Self::char8_t,
+            "char16_t" => Self::char16_t,
+            "char32_t" => Self::char32_t,
             "::core::ffi::c_char" => Self::c_char,
             "::core::ffi::c_uchar" => Self::c_uchar,
             "::core::ffi::c_schar" => Self::c_schar,
@@ -278,10 +383,15 @@ impl ToTokens for PrimitiveType {
             Self::i32 => quote! {i32},
             Self::u64 => quote! {u64},
             Self::i64 => quote! {i64},
+            Self::u128 => quote! {u128},
+            Self::i128 => quote! {i128},
             Self::usize => quote! {usize},
             Self::isize => quote! {isize},
             Self::f32 => quote! {f32},
             Self::f64 => quote! {f64},
+            Self::char8_t => quote! {u8},
+            Self::char16_t => quote! {u16},
+            Self::char32_t => quote! {u32},
             Self::c_char => quote! {::core::ffi::c_char},
             Self::c_uchar => quote! {::core::ffi::c_uchar},
             Self::c_schar => quote! {::core::ffi::c_schar},
@@ -339,6 +449,11 @@ pub enum RsTypeKind {
         abi: Rc<str>,
         return_type: Rc<RsTypeKind>,
         param_types: Rc<[RsTypeKind]>,
+        /// Lifetimes universally quantified by a `for<'a, ...>` binder on this
+        /// function pointer, e.g. the `'a` and `'b` in a C++
+        /// `void (*)(const A&, const B&)`'s independently-scoped parameter
+        /// lifetimes. Empty if the pointer carries no lifetime binder.
+        bound_lifetimes: Rc<[Lifetime]>,
     },
     /// An incomplete record type.
     IncompleteRecord {
@@ -350,6 +465,12 @@ pub enum RsTypeKind {
         record: Rc<Record>,
         crate_path: Rc<CratePath>,
         known_generic_monomorphization: Option<Rc<GenericMonomorphization>>,
+        /// Whether `self` is backed by an opaque, heap/placement-allocated
+        /// handle (see `RsTypeKind::as_opaque_by_value`) rather than by a
+        /// value whose Rust layout faithfully replicates `record`'s C++
+        /// layout. Opaque handles are move-only: unlike a normal `Record`,
+        /// they're never `Copy`, and aren't treated as `Unpin`.
+        is_opaque_handle: bool,
     },
     Enum {
         enum_: Rc<Enum>,
@@ -375,8 +496,17 @@ pub enum RsTypeKind {
         type_args: Rc<[RsTypeKind]>,
         is_same_abi: bool,
     },
+    /// A placeholder used only in *patterns* passed to `could_unify`, never in
+    /// a real lowered type. See `could_unify` for how it's matched.
+    Placeholder(PlaceholderId),
 }
 
+/// Identifies a `RsTypeKind::Placeholder` within a pattern, e.g. the `$T` in
+/// "any `std::unique_ptr<$T, std::default_delete<$T>>`". Two placeholders
+/// with the same id occurring in one pattern must unify to the same type.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PlaceholderId(pub Rc<str>);
+
 impl RsTypeKind {
     pub fn new_record(db: &dyn BindingsGenerator, record: Rc<Record>, ir: &IR) -> Result<Self> {
         let crate_path = Rc::new(CratePath::new(
@@ -386,7 +516,12 @@ impl RsTypeKind {
         ));
         let known_generic_monomorphization =
             map_to_supported_generic(db, &record.template_specialization).map(Rc::new);
-        Ok(RsTypeKind::Record { record, crate_path, known_generic_monomorphization })
+        Ok(RsTypeKind::Record {
+            record,
+            crate_path,
+            known_generic_monomorphization,
+            is_opaque_handle: false,
+        })
     }
 
     pub fn new_enum(enum_: Rc<Enum>, ir: &IR) -> Result<Self> {
@@ -446,8 +581,12 @@ impl RsTypeKind {
     pub fn is_unpin(&self) -> bool {
         match self {
             RsTypeKind::IncompleteRecord { .. } => false,
-            RsTypeKind::Record { record, known_generic_monomorphization, .. } => {
-                known_generic_monomorphization.is_some() || record.is_unpin()
+            RsTypeKind::Record { record, known_generic_monomorphization, is_opaque_handle, .. } => {
+                // An opaque handle is modeled as move-only and `!Unpin` (see
+                // `as_opaque_by_value`), so mutable access always goes through
+                // `Pin<&mut Self>`, the same as any other non-relocatable record.
+                !*is_opaque_handle
+                    && (known_generic_monomorphization.is_some() || record.is_unpin())
             }
             RsTypeKind::TypeAlias { underlying_type, .. } => underlying_type.is_unpin(),
             RsTypeKind::BridgeType { .. } => true,
@@ -553,14 +692,21 @@ impl RsTypeKind {
                 // them with opaque blobs.
                 //
                 // Instead, what matters is the abstract properties of the struct itself!
-                RsTypeKind::Record { record, .. } => {
+                RsTypeKind::Record { record, known_generic_monomorphization, .. } => {
                     // Types which aren't rust-movable, or which are general template
                     // instantiations, are only supported experimentally.
                     // But we do want to allow some commonly used template instantiations such as
                     // std::string_view so we create an allow list fo them. This is just a temporary
                     // solution until we have a better way to handle template
                     // instantiations.
-                    if record.defining_target.is_none()
+                    if known_generic_monomorphization.is_some() {
+                        // A record with a recognized generic monomorphization (e.g.
+                        // `std::vector<T>` -> `Vec<T>`) is rendered as the mapped Rust generic
+                        // type, not as the raw template instantiation, so it's fully supported
+                        // (its type arguments' own features are still checked, since they're
+                        // visited separately by `dfs_iter`).
+                        require_feature(CrubitFeature::Supported, None)
+                    } else if record.defining_target.is_none()
                         || TEMPLATE_INSTANTIATION_ALLOWLIST
                             .contains(&record.cc_preferred_name.as_ref())
                     {
@@ -582,37 +728,65 @@ impl RsTypeKind {
                 RsTypeKind::BridgeType { .. } => require_feature(CrubitFeature::Experimental, None),
                 // Fallback case, we can't really give a good error message here.
                 RsTypeKind::Other { .. } => require_feature(CrubitFeature::Experimental, None),
+                // Placeholders only occur in patterns passed to `could_unify`, never in a
+                // real lowered type that would reach this function.
+                RsTypeKind::Placeholder(_) => {}
             }
         }
         (missing_features, reasons.into_iter().join(", "))
     }
 
-    /// Returns true if the type can be passed by value through `extern "C"` ABI
-    /// thunks.
+    /// Returns true if the type's Rust layout is a faithful enough copy of its C++ layout to
+    /// be passed directly as an `extern "C"` by-value parameter or return type, with no
+    /// thunk involved.
+    ///
+    /// Not currently read by call-site codegen -- see the note on `ByValueStrategy`.
     pub fn is_c_abi_compatible_by_value(&self) -> bool {
         match self {
-            RsTypeKind::TypeAlias { underlying_type, .. } => {
-                underlying_type.is_c_abi_compatible_by_value()
-            }
+            RsTypeKind::Other { is_same_abi, .. } => *is_same_abi,
+            _ => matches!(self.by_value_strategy(), ByValueStrategy::Direct),
+        }
+    }
+
+    /// Classifies which ABI strategy would be needed to pass `self` by value across the
+    /// `extern "C"` boundary.
+    ///
+    /// See `ByValueStrategy` for what each variant means, and why `Thunk` isn't acted on yet.
+    pub fn by_value_strategy(&self) -> ByValueStrategy {
+        match self {
+            RsTypeKind::TypeAlias { underlying_type, .. } => underlying_type.by_value_strategy(),
             RsTypeKind::IncompleteRecord { .. } => {
                 // Incomplete record (forward declaration) as parameter type or return type is
                 // unusual but it's a valid cc_library and such a header can be made to work
                 // when its user code includes headers that define the forward-declared type.
-                // Thus we don't panic here and simply return false, to allow
+                // Thus we don't panic here and simply fall back to `Thunk`, to allow
                 // Crubit to generate bindings for other un-impacted APIs.
-                false
+                ByValueStrategy::Thunk
             }
-            // `rs_bindings_from_cc` can change the type of fields (e.g. using a blob of bytes for
-            // unsupported field types, or for no_unique_address fields).  Changing the type
-            // of fields may change the ABI, which means that we can no longer assume
-            // that `extern "C"` ABI thunks can pass such types by value.
-            //
-            // TODO(b/274177296): Return `true` for structs where bindings replicate the type of
-            // all the fields.
-            RsTypeKind::Record { .. } => false,
-            RsTypeKind::BridgeType { .. } => false,
-            RsTypeKind::Other { is_same_abi, .. } => *is_same_abi,
-            _ => true,
+            RsTypeKind::Record { record, .. } => {
+                // A record can only be passed by value directly if Rust's generated layout
+                // is guaranteed to be a faithful, movable copy of the C++ value: it must be
+                // `Unpin` (so there's no separate in-place-construction step to run), its
+                // copy constructor and destructor must be trivial (so the bytes alone,
+                // without calling into C++, fully determine the value), and every field's
+                // Rust type must be a verified faithful, byte-for-byte copy of its C++
+                // type. That last check is what rules out `rs_bindings_from_cc` having
+                // reshaped some field into an opaque byte blob (e.g. for an unsupported
+                // field type, or for a `no_unique_address` member) -- which would make
+                // raw-byte `Direct` passing unsound even for an otherwise-trivial record.
+                if !record.is_union()
+                    && record.is_unpin()
+                    && record.copy_constructor == ir::SpecialMemberFunc::Trivial
+                    && record.destructor == ir::SpecialMemberFunc::Trivial
+                    && record.fields.iter().all(field_is_verified_faithful_copy)
+                {
+                    ByValueStrategy::Direct
+                } else {
+                    ByValueStrategy::Thunk
+                }
+            }
+            RsTypeKind::BridgeType { .. } => ByValueStrategy::Thunk,
+            _ => ByValueStrategy::Direct,
         }
     }
 
@@ -645,6 +819,32 @@ impl RsTypeKind {
         }
     }
 
+    /// Returns `self` re-modeled as an opaque, heap/placement-allocated
+    /// handle, for use when `check_by_value` rejects `self` outright (e.g. a
+    /// non-trivially-relocatable C++ record with a non-public destructor or
+    /// that's abstract).
+    ///
+    /// The returned `RsTypeKind` is move-only: `implements_copy` is always
+    /// `false` and `is_unpin` is always `false` for it, regardless of what
+    /// they'd otherwise return for the same underlying record. Returns `None`
+    /// if `self` isn't a record, or if it already passes `check_by_value` (in
+    /// which case there's no need for an opaque fallback).
+    pub fn as_opaque_by_value(&self) -> Option<RsTypeKind> {
+        match self {
+            RsTypeKind::Record { record, crate_path, known_generic_monomorphization, .. }
+                if check_by_value(record).is_err() =>
+            {
+                Some(RsTypeKind::Record {
+                    record: record.clone(),
+                    crate_path: crate_path.clone(),
+                    known_generic_monomorphization: known_generic_monomorphization.clone(),
+                    is_opaque_handle: true,
+                })
+            }
+            _ => None,
+        }
+    }
+
     pub fn format_as_return_type_fragment(&self, self_record: Option<&Record>) -> TokenStream {
         match self {
             RsTypeKind::Primitive(PrimitiveType::Unit) => quote! {},
@@ -657,11 +857,15 @@ impl RsTypeKind {
 
     /// Formats this RsTypeKind as `&'a mut MaybeUninit<SomeStruct>`. This is
     /// used to format `__this` parameter in a constructor thunk.
-    pub fn format_mut_ref_as_uninitialized(&self) -> Result<TokenStream> {
+    pub fn format_mut_ref_as_uninitialized(&self) -> Result<RsSnippet> {
         match self {
             RsTypeKind::Reference { referent, lifetime, mutability: Mutability::Mut } => {
                 let lifetime = lifetime.format_for_reference();
-                Ok(quote! { & #lifetime mut ::core::mem::MaybeUninit< #referent > })
+                Ok(RsSnippet {
+                    tokens: quote! { & #lifetime mut MaybeUninit< #referent > },
+                    features: HashSet::new(),
+                    imports: [UsePath::new("core::mem::MaybeUninit")].into_iter().collect(),
+                })
             }
             _ => bail!("Expected reference to format as MaybeUninit, got: {:?}", self),
         }
@@ -687,9 +891,11 @@ impl RsTypeKind {
                 let mut_ = mutability.format_for_reference();
                 let lifetime = lifetime.format_for_reference();
                 if mutability == &Mutability::Mut && !referent.is_unpin() {
-                    // TODO(b/239661934): Add a `use ::core::pin::Pin` to the crate, and use
-                    // `Pin`.
-                    Ok(RsSnippet::new(quote! {self: ::core::pin::Pin< & #lifetime #mut_ Self>}))
+                    Ok(RsSnippet {
+                        tokens: quote! {self: Pin< & #lifetime #mut_ Self>},
+                        features: HashSet::new(),
+                        imports: [UsePath::new("core::pin::Pin")].into_iter().collect(),
+                    })
                 } else {
                     Ok(RsSnippet::new(quote! { & #lifetime #mut_ self }))
                 }
@@ -697,15 +903,16 @@ impl RsTypeKind {
             RsTypeKind::RvalueReference { referent: _, lifetime, mutability } => {
                 let lifetime = lifetime.format_for_reference();
                 let arbitrary_self_types = make_rs_ident("arbitrary_self_types");
-                // TODO(b/239661934): Add `use ::ctor::{RvalueReference, ConstRvalueReference}`.
                 match mutability {
                     Mutability::Mut => Ok(RsSnippet {
-                        tokens: quote! {self: ::ctor::RvalueReference<#lifetime, Self>},
+                        tokens: quote! {self: RvalueReference<#lifetime, Self>},
                         features: [arbitrary_self_types].into_iter().collect(),
+                        imports: [UsePath::new("ctor::RvalueReference")].into_iter().collect(),
                     }),
                     Mutability::Const => Ok(RsSnippet {
-                        tokens: quote! {self: ::ctor::ConstRvalueReference<#lifetime, Self>},
+                        tokens: quote! {self: ConstRvalueReference<#lifetime, Self>},
                         features: [arbitrary_self_types].into_iter().collect(),
+                        imports: [UsePath::new("ctor::ConstRvalueReference")].into_iter().collect(),
                     }),
                 }
             }
@@ -729,6 +936,7 @@ impl RsTypeKind {
             RsTypeKind::Reference { mutability: Mutability::Mut, .. } => false,
             RsTypeKind::RvalueReference { .. } => false,
             RsTypeKind::IncompleteRecord { .. } => false,
+            RsTypeKind::Record { is_opaque_handle: true, .. } => false,
             RsTypeKind::Record { record, .. } => should_derive_copy(record),
             RsTypeKind::Enum { .. } => true,
             RsTypeKind::TypeAlias { underlying_type, .. } => underlying_type.implements_copy(),
@@ -743,6 +951,98 @@ impl RsTypeKind {
                 // of their `type_args` are `Copy`.
                 type_args.iter().all(|t| t.implements_copy())
             }
+            // Placeholders only occur in patterns, never in a real lowered type.
+            RsTypeKind::Placeholder(_) => false,
+        }
+    }
+
+    /// Returns whether `self` structurally unifies with `pattern`, a type
+    /// that may contain `RsTypeKind::Placeholder`s standing in for arbitrary
+    /// subtypes (e.g. as used by user-defined bridging rules to match "any
+    /// `std::vector<$T>`").
+    ///
+    /// Placeholders in `pattern` are bound in `subst` as they're matched; a
+    /// placeholder that recurs in `pattern` must unify with the same type
+    /// every time it's encountered. `subst` is left partially populated if
+    /// unification fails partway through.
+    pub fn could_unify(&self, pattern: &RsTypeKind, subst: &mut HashMap<PlaceholderId, RsTypeKind>) -> bool {
+        if let RsTypeKind::Placeholder(id) = pattern {
+            return match subst.get(id) {
+                Some(bound) => bound == self,
+                None => {
+                    subst.insert(id.clone(), self.clone());
+                    true
+                }
+            };
+        }
+        match (self, pattern) {
+            (
+                RsTypeKind::Pointer { pointee, mutability },
+                RsTypeKind::Pointer { pointee: pattern_pointee, mutability: pattern_mutability },
+            ) => mutability == pattern_mutability && pointee.could_unify(pattern_pointee, subst),
+            (
+                RsTypeKind::Reference { referent, mutability, .. },
+                RsTypeKind::Reference { referent: pattern_referent, mutability: pattern_mutability, .. },
+            ) => mutability == pattern_mutability && referent.could_unify(pattern_referent, subst),
+            (
+                RsTypeKind::RvalueReference { referent, mutability, .. },
+                RsTypeKind::RvalueReference {
+                    referent: pattern_referent,
+                    mutability: pattern_mutability,
+                    ..
+                },
+            ) => mutability == pattern_mutability && referent.could_unify(pattern_referent, subst),
+            (RsTypeKind::Slice(t), RsTypeKind::Slice(pattern_t)) => t.could_unify(pattern_t, subst),
+            (RsTypeKind::Option(t), RsTypeKind::Option(pattern_t)) => t.could_unify(pattern_t, subst),
+            (
+                RsTypeKind::FuncPtr { abi, return_type, param_types, .. },
+                RsTypeKind::FuncPtr {
+                    abi: pattern_abi,
+                    return_type: pattern_return_type,
+                    param_types: pattern_param_types,
+                    ..
+                },
+            ) => {
+                abi == pattern_abi
+                    && param_types.len() == pattern_param_types.len()
+                    && return_type.could_unify(pattern_return_type, subst)
+                    && std::iter::zip(param_types.iter(), pattern_param_types.iter())
+                        .all(|(t, pattern_t)| t.could_unify(pattern_t, subst))
+            }
+            (
+                RsTypeKind::Other { name, type_args, .. },
+                RsTypeKind::Other { name: pattern_name, type_args: pattern_type_args, .. },
+            ) => {
+                name == pattern_name
+                    && type_args.len() == pattern_type_args.len()
+                    && std::iter::zip(type_args.iter(), pattern_type_args.iter())
+                        .all(|(t, pattern_t)| t.could_unify(pattern_t, subst))
+            }
+            (
+                RsTypeKind::Record { record, known_generic_monomorphization, .. },
+                RsTypeKind::Record {
+                    record: pattern_record,
+                    known_generic_monomorphization: pattern_known_generic_monomorphization,
+                    ..
+                },
+            ) => {
+                record.id == pattern_record.id
+                    && match (known_generic_monomorphization, pattern_known_generic_monomorphization) {
+                        (Some(monomorphization), Some(pattern_monomorphization)) => {
+                            monomorphization.type_args.len() == pattern_monomorphization.type_args.len()
+                                && std::iter::zip(
+                                    monomorphization.type_args.iter(),
+                                    pattern_monomorphization.type_args.iter(),
+                                )
+                                .all(|(t, pattern_t)| t.could_unify(pattern_t, subst))
+                        }
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            // Every other pairing (including mismatched variants) has no substructure left to
+            // recurse into, so fall back to plain equality.
+            _ => self == pattern,
         }
     }
 
@@ -789,8 +1089,20 @@ impl RsTypeKind {
     /// Iterates over all `LifetimeId`s in `self` and in all the nested types.
     /// Note that the results might contain duplicate LifetimeId values (e.g.
     /// if the same LifetimeId is used in two `type_args`).
+    ///
+    /// Lifetimes bound by a `for<'a>` binder on a nested `FuncPtr` (see
+    /// `RsTypeKind::FuncPtr::bound_lifetimes`) are universally quantified by
+    /// that binder, not free in `self`, so they're excluded here.
     pub fn lifetimes(&self) -> impl Iterator<Item = Lifetime> + '_ {
-        self.dfs_iter().filter_map(Self::lifetime)
+        let bound: HashSet<Lifetime> = self
+            .dfs_iter()
+            .filter_map(|ty| match ty {
+                RsTypeKind::FuncPtr { bound_lifetimes, .. } => Some(bound_lifetimes.iter().cloned()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        self.dfs_iter().filter_map(Self::lifetime).filter(move |lifetime| !bound.contains(lifetime))
     }
 
     /// Returns the pointer or reference target.
@@ -827,10 +1139,10 @@ impl RsTypeKind {
                 let referent_ = referent.to_token_stream_replacing_by_self(self_record);
                 let reference = quote! {& #lifetime #mut_ #referent_};
                 if mutability == &Mutability::Mut && !referent.is_unpin() {
-                    // TODO(b/239661934): Add a `use ::core::pin::Pin` to the crate, and use
-                    // `Pin`. This either requires deciding how to qualify pin at
-                    // RsTypeKind-creation time, or returning a non-TokenStream type from here (and
-                    // not implementing ToTokens, but instead some other interface.)
+                    // TODO(b/239661934): `RsSnippet::imports` (see `format_as_self_param`) tracks
+                    // this for the `self`-parameter case; doing the same here would require this
+                    // method (constrained by the `ToTokens::to_token_stream` signature it
+                    // implements) to return something other than a bare `TokenStream`.
                     quote! {::core::pin::Pin< #reference >}
                 } else {
                     reference
@@ -838,14 +1150,14 @@ impl RsTypeKind {
             }
             RsTypeKind::RvalueReference { referent, mutability, lifetime } => {
                 let referent_ = referent.to_token_stream_replacing_by_self(self_record);
-                // TODO(b/239661934): Add a `use ::ctor::RvalueReference` (etc.) to the crate.
+                // TODO(b/239661934): see the `Pin` TODO above; same limitation applies here.
                 if mutability == &Mutability::Mut {
                     quote! {::ctor::RvalueReference<#lifetime, #referent_>}
                 } else {
                     quote! {::ctor::ConstRvalueReference<#lifetime, #referent_>}
                 }
             }
-            RsTypeKind::FuncPtr { abi, return_type, param_types } => {
+            RsTypeKind::FuncPtr { abi, return_type, param_types, bound_lifetimes } => {
                 let param_types_: Vec<TokenStream> = param_types
                     .iter()
                     .map(|type_| type_.to_token_stream_replacing_by_self(self_record))
@@ -856,7 +1168,8 @@ impl RsTypeKind {
                 } else {
                     quote! {}
                 };
-                quote! { #unsafe_ extern #abi fn( #( #param_types_ ),* ) #return_frag }
+                let binder = format_lifetime_binder(bound_lifetimes.iter());
+                quote! { #binder #unsafe_ extern #abi fn( #( #param_types_ ),* ) #return_frag }
             }
             RsTypeKind::Record { record, .. } => {
                 if self_record == Some(record) {
@@ -901,8 +1214,120 @@ impl std::fmt::Display for RsTypeKind {
     }
 }
 
+/// Returns true if `path`'s `::`-separated segments equal `expected_segments`
+/// exactly, with no segment carrying generic arguments of its own (i.e. none
+/// containing `<`).
+///
+/// Borrows the path-matching approach of LDK's c-bindings-gen
+/// `path_matches_nongeneric`: a plain segment-by-segment comparison that
+/// conservatively rejects anything that looks parameterized, rather than
+/// trying to parse and compare generic argument lists.
+fn path_matches_nongeneric(path: &str, expected_segments: &[&str]) -> bool {
+    let segments: Vec<&str> = path.split("::").collect();
+    segments.len() == expected_segments.len()
+        && std::iter::zip(&segments, expected_segments)
+            .all(|(actual, expected)| actual == expected && !actual.contains('<'))
+}
+
+/// A declarative constraint on one of a template's "ignored" (non-type-arg)
+/// positions, generalizing `std::unique_ptr`'s requirement that its deleter
+/// be `std::default_delete<T>` for the same `T` as its element type.
+struct NestedSpecializationRequirement {
+    /// The ignored position being constrained (e.g. 1, for `unique_ptr`'s
+    /// deleter).
+    position: usize,
+    /// The expected template name at that position, matched via
+    /// `path_matches_nongeneric` (e.g. `&["std", "default_delete"]`).
+    expected_template_name: &'static [&'static str],
+    /// The position of the type argument that the nested specialization's
+    /// own sole template argument must equal (e.g. 0, `unique_ptr`'s element
+    /// type).
+    must_match_type_arg_position: usize,
+}
+
+/// A statically configured rule for recognizing a supported C++ template
+/// instantiation and translating it to a Rust generic type, consulted by
+/// `map_to_supported_generic`. Projects that want to translate additional
+/// C++ templates to Rust generics can append their own entries to
+/// `TEMPLATE_GENERIC_MAPPINGS` without touching the lowering logic below.
+struct TemplateGenericMapping {
+    /// The template's fully qualified C++ name, matched via
+    /// `path_matches_nongeneric` (e.g. `&["std", "vector"]`).
+    template_name: &'static [&'static str],
+    /// The expected number of template arguments, e.g. 2 for
+    /// `std::vector<T, Allocator>`. A specialization with a different number
+    /// of arguments doesn't match this entry.
+    arity: usize,
+    /// The 0-based positions, in order, of the template arguments that
+    /// become the translated type's Rust generic arguments. Positions not
+    /// listed here (e.g. `std::vector`'s allocator) are still lowered, but
+    /// aren't part of the translated type (other than being checked by
+    /// `nested_requirements`).
+    type_arg_positions: &'static [usize],
+    /// The name of the corresponding Rust generic type, e.g. `"Vec"`
+    /// (rendered as `Vec<T>`), or `""` to render the type arguments as a
+    /// Rust tuple instead (e.g. `std::pair<A, B>` -> `(A, B)`).
+    rust_generic_name: &'static str,
+    /// Declarative constraints on the ignored positions, beyond name and
+    /// arity (e.g. confirming `std::unique_ptr`'s deleter is the default
+    /// one). All of these must hold for the mapping to apply.
+    nested_requirements: &'static [NestedSpecializationRequirement],
+}
+
+/// Built-in template mappings. Projects that want to translate additional
+/// C++ templates to Rust generics can append their own entries here.
+static TEMPLATE_GENERIC_MAPPINGS: &[TemplateGenericMapping] = &[
+    TemplateGenericMapping {
+        template_name: &["std", "vector"],
+        arity: 2,
+        type_arg_positions: &[0],
+        rust_generic_name: "Vec",
+        nested_requirements: &[],
+    },
+    TemplateGenericMapping {
+        template_name: &["std", "optional"],
+        arity: 1,
+        type_arg_positions: &[0],
+        rust_generic_name: "Option",
+        nested_requirements: &[],
+    },
+    TemplateGenericMapping {
+        template_name: &["std", "pair"],
+        arity: 2,
+        type_arg_positions: &[0, 1],
+        rust_generic_name: "",
+        nested_requirements: &[],
+    },
+    TemplateGenericMapping {
+        template_name: &["std", "shared_ptr"],
+        arity: 1,
+        type_arg_positions: &[0],
+        rust_generic_name: "cc_std::std::shared_ptr",
+        nested_requirements: &[],
+    },
+    TemplateGenericMapping {
+        template_name: &["std", "basic_string"],
+        arity: 3,
+        type_arg_positions: &[0],
+        rust_generic_name: "cc_std::std::basic_string",
+        nested_requirements: &[],
+    },
+    TemplateGenericMapping {
+        template_name: &["std", "unique_ptr"],
+        arity: 2,
+        type_arg_positions: &[0],
+        rust_generic_name: "cc_std::std::unique_ptr",
+        nested_requirements: &[NestedSpecializationRequirement {
+            position: 1,
+            expected_template_name: &["std", "default_delete"],
+            must_match_type_arg_position: 0,
+        }],
+    },
+];
+
 /// Returns the Rust generic information if:
-/// - it is a known and supported template specialization.
+/// - it is a known and supported template specialization, per
+///   `TEMPLATE_GENERIC_MAPPINGS`.
 /// - all of the template argument types are supported.
 pub fn map_to_supported_generic(
     db: &dyn BindingsGenerator,
@@ -910,7 +1335,14 @@ pub fn map_to_supported_generic(
 ) -> Option<GenericMonomorphization> {
     let template_specialization = template_specialization.as_ref()?;
     let template_name = template_specialization.template_name.to_string();
-    let mut type_args = Vec::new();
+    let mapping = TEMPLATE_GENERIC_MAPPINGS
+        .iter()
+        .find(|m| path_matches_nongeneric(&template_name, m.template_name))?;
+    if template_specialization.template_args.len() != mapping.arity {
+        return None;
+    }
+
+    let mut lowered_args = Vec::new();
     for arg in template_specialization.template_args.iter() {
         if arg.type_.is_err() {
             return None;
@@ -920,27 +1352,32 @@ pub fn map_to_supported_generic(
         if arg_type_kind.is_err() {
             return None;
         }
-        type_args.push(arg_type_kind.unwrap());
+        lowered_args.push(arg_type_kind.unwrap());
     }
 
-    let rust_generic_name = match (template_name.as_str(), &type_args[..]) {
-        ("std::unique_ptr", [_t, RsTypeKind::Record { record, .. }]) => {
-            let deleter = record.template_specialization.as_ref()?;
-            let template_name = deleter.template_name.to_string();
-            if template_name != "std::default_delete"
-                || deleter.template_args.len() != 1
-                || deleter.template_args[0] != template_specialization.template_args[0]
-            {
-                return None;
-            }
-            "cc_std::std::unique_ptr"
+    for requirement in mapping.nested_requirements {
+        let RsTypeKind::Record { record, .. } = &lowered_args[requirement.position] else {
+            return None;
+        };
+        let Some(nested) = record.template_specialization.as_ref() else {
+            return None;
+        };
+        let nested_name = nested.template_name.to_string();
+        let expected_arg =
+            &template_specialization.template_args[requirement.must_match_type_arg_position];
+        if !path_matches_nongeneric(&nested_name, requirement.expected_template_name)
+            || nested.template_args.len() != 1
+            || nested.template_args[0] != *expected_arg
+        {
+            return None;
         }
-        _ => return None,
-    };
+    }
+
+    let type_args = mapping.type_arg_positions.iter().map(|&i| lowered_args[i].clone()).collect();
 
     Some(GenericMonomorphization {
         template_name: template_name.into(),
-        rust_generic_name: rust_generic_name.into(),
+        rust_generic_name: mapping.rust_generic_name.into(),
         type_args,
     })
 }
@@ -961,46 +1398,50 @@ impl ToTokens for RsTypeKind {
                 let lifetime = lifetime.format_for_reference();
                 let reference = quote! {& #lifetime #mut_ #referent};
                 if mutability == &Mutability::Mut && !referent.is_unpin() {
-                    // TODO(b/239661934): Add a `use ::core::pin::Pin` to the crate, and use
-                    // `Pin`. This either requires deciding how to qualify pin at
-                    // RsTypeKind-creation time, or returning a non-TokenStream type from here (and
-                    // not implementing ToTokens, but instead some other interface.)
+                    // TODO(b/239661934): see the matching TODO in
+                    // `to_token_stream_replacing_by_self`; this method has the same
+                    // `ToTokens`-mandated signature and so the same limitation.
                     quote! {::core::pin::Pin< #reference >}
                 } else {
                     reference
                 }
             }
             RsTypeKind::RvalueReference { referent, mutability, lifetime } => {
-                // TODO(b/239661934): Add a `use ::ctor::RvalueReference` (etc.) to the crate.
+                // TODO(b/239661934): see the `Pin` TODO above; same limitation applies here.
                 if mutability == &Mutability::Mut {
                     quote! {::ctor::RvalueReference<#lifetime, #referent>}
                 } else {
                     quote! {::ctor::ConstRvalueReference<#lifetime, #referent>}
                 }
             }
-            RsTypeKind::FuncPtr { abi, return_type, param_types } => {
+            RsTypeKind::FuncPtr { abi, return_type, param_types, bound_lifetimes } => {
                 let return_frag = return_type.format_as_return_type_fragment(None);
                 let unsafe_ = if param_types.iter().any(|p| p.is_unsafe()) {
                     quote! {unsafe}
                 } else {
                     quote! {}
                 };
-                quote! { #unsafe_ extern #abi fn( #( #param_types ),* ) #return_frag }
+                let binder = format_lifetime_binder(bound_lifetimes.iter());
+                quote! { #binder #unsafe_ extern #abi fn( #( #param_types ),* ) #return_frag }
             }
             RsTypeKind::IncompleteRecord { incomplete_record, crate_path } => {
                 let record_ident = make_rs_ident(incomplete_record.rs_name.as_ref());
                 quote! { #crate_path #record_ident }
             }
-            RsTypeKind::Record { record, crate_path, known_generic_monomorphization } => {
+            RsTypeKind::Record { record, crate_path, known_generic_monomorphization, .. } => {
                 if let Some(known_generic_monomorphization) = known_generic_monomorphization {
                     let inner_types_str = known_generic_monomorphization
                         .type_args
                         .iter()
                         .map(|t| t.to_token_stream())
-                        .take(1)
                         .collect::<Vec<_>>();
                     let rust_generic_name =
                         known_generic_monomorphization.rust_generic_name.as_ref();
+                    if rust_generic_name.is_empty() {
+                        // An empty name designates a tuple translation (e.g.
+                        // `std::pair<A, B>` -> `(A, B)`), rather than a named generic.
+                        return quote! { (#(#inner_types_str),*) };
+                    }
                     let rust_generic_name_parts: Vec<_> =
                         rust_generic_name.split("::").map(make_rs_ident).collect();
                     return quote! { #(#rust_generic_name_parts)::* <#(#inner_types_str),*>};
@@ -1035,10 +1476,126 @@ impl ToTokens for RsTypeKind {
                     format_generic_params(/* lifetimes= */ &[], type_args.iter());
                 quote! {#name #generic_params}
             }
+            RsTypeKind::Placeholder(id) => {
+                unreachable!(
+                    "Placeholder {id:?} should never reach codegen; it only exists in patterns \
+                     passed to `could_unify`"
+                )
+            }
         }
     }
 }
 
+/// One function to be resolved at runtime by
+/// `generate_dynamic_library_bindings`, rather than linked against statically.
+pub struct DynamicLibraryFunction {
+    /// The name of the generated wrapper method, kept verbatim from C++
+    /// (e.g. `"Add"`), not converted to `snake_case`.
+    pub name: Rc<str>,
+    /// The symbol to resolve via `dlsym`, e.g. the mangled thunk name
+    /// `__rust_thunk___Z3Addii`.
+    pub symbol: Rc<str>,
+    /// This function's C-ABI signature. Must be `RsTypeKind::FuncPtr`.
+    pub func_ptr: RsTypeKind,
+}
+
+/// Generates a struct (and its `impl`) that resolves `functions` from a
+/// shared library opened at runtime via `dlopen`/`dlsym`, instead of linking
+/// against them statically through `extern "C"`. This lets a crate bind a
+/// C++ library that must be `dlopen`ed (plugins, optional dependencies)
+/// rather than linked at build time.
+///
+/// `struct_name` becomes the name of the generated struct. Each entry of
+/// `functions` becomes a private function-pointer field plus a public
+/// wrapper method of the same name that forwards to it, typed via the
+/// existing `RsTypeKind::FuncPtr` ABI formatting.
+pub fn generate_dynamic_library_bindings(
+    struct_name: &str,
+    functions: &[DynamicLibraryFunction],
+) -> Result<TokenStream> {
+    let struct_ident = make_rs_ident(struct_name);
+    let mut fields = vec![];
+    let mut field_idents = vec![];
+    let mut loads = vec![];
+    let mut wrappers = vec![];
+    for function in functions {
+        let RsTypeKind::FuncPtr { abi, return_type, param_types, .. } = &function.func_ptr else {
+            bail!("`{}` is not backed by a function pointer type", function.name);
+        };
+        let field_ident = make_rs_ident(&function.name);
+        let symbol = &*function.symbol;
+        let param_idents: Vec<Ident> =
+            (0..param_types.len()).map(|i| make_rs_ident(&format!("__param_{i}"))).collect();
+        let return_frag = return_type.format_as_return_type_fragment(None);
+        let fn_ptr_type = quote! { unsafe extern #abi fn( #( #param_types ),* ) #return_frag };
+
+        fields.push(quote! { #field_ident: #fn_ptr_type });
+        field_idents.push(field_ident.clone());
+        // `dlsym` returns null both for "symbol resolves to a null address" and for
+        // "no such symbol", but C++ functions are never actually loaded at address
+        // zero, so treating null as "missing" is safe and lets us report a useful
+        // error instead of a null-pointer call down the line.
+        loads.push(quote! {
+            let __symbol = dlsym(__handle, concat!(#symbol, "\0").as_ptr().cast());
+            if __symbol.is_null() {
+                dlclose(__handle);
+                return Err(format!("symbol `{}` not found in `{:?}`", #symbol, path));
+            }
+            let #field_ident: #fn_ptr_type = ::core::mem::transmute(__symbol);
+        });
+        wrappers.push(quote! {
+            pub unsafe fn #field_ident(&self, #( #param_idents: #param_types ),* ) #return_frag {
+                (self.#field_ident)( #( #param_idents ),* )
+            }
+        });
+    }
+    Ok(quote! {
+        // `dlopen`/`dlsym`/`dlclose` are declared by hand here (rather than pulled
+        // in from a crate) so that this dynamic-loading mode doesn't add a
+        // third-party dependency to the generated crate.
+        extern "C" {
+            fn dlopen(filename: *const ::core::ffi::c_char, flag: ::core::ffi::c_int) -> *mut ::core::ffi::c_void;
+            fn dlsym(handle: *mut ::core::ffi::c_void, symbol: *const ::core::ffi::c_char) -> *mut ::core::ffi::c_void;
+            fn dlclose(handle: *mut ::core::ffi::c_void) -> ::core::ffi::c_int;
+        }
+
+        /// Bindings for a C++ library loaded at runtime (rather than linked
+        /// statically), via `dlopen`.
+        pub struct #struct_ident {
+            __handle: *mut ::core::ffi::c_void,
+            #( #fields, )*
+        }
+
+        impl #struct_ident {
+            /// Opens the shared library at `path` and resolves every function listed
+            /// below, returning an error naming the first symbol that's missing.
+            pub fn open(path: &::std::ffi::CStr) -> Result<Self, String> {
+                unsafe {
+                    // `2` is `RTLD_NOW`, found empirically to be portable across the
+                    // Linux libcs this is tested against; like the rest of this
+                    // module, it may need conditional compilation for other platforms.
+                    let __handle = dlopen(path.as_ptr(), 2);
+                    if __handle.is_null() {
+                        return Err(format!("failed to dlopen `{:?}`", path));
+                    }
+                    #( #loads )*
+                    Ok(Self { __handle, #( #field_idents ),* })
+                }
+            }
+
+            #( #wrappers )*
+        }
+
+        impl Drop for #struct_ident {
+            fn drop(&mut self) {
+                unsafe {
+                    dlclose(self.__handle);
+                }
+            }
+        }
+    })
+}
+
 struct RsTypeKindIter<'ty> {
     todo: Vec<&'ty RsTypeKind>,
 }
@@ -1059,8 +1616,17 @@ impl<'ty> Iterator for RsTypeKindIter<'ty> {
                 match curr {
                     RsTypeKind::Primitive { .. }
                     | RsTypeKind::IncompleteRecord { .. }
-                    | RsTypeKind::Record { .. }
                     | RsTypeKind::Enum { .. } => {}
+                    RsTypeKind::Record { known_generic_monomorphization, .. } => {
+                        // The record itself doesn't recurse into its fields (see the comment
+                        // on `required_crubit_features`), but a known generic
+                        // monomorphization's type arguments (e.g. the `T` in a `std::vector<T>`
+                        // translated to `Vec<T>`) are rendered inline, so their own feature
+                        // requirements need to be visited too.
+                        if let Some(monomorphization) = known_generic_monomorphization {
+                            self.todo.extend(monomorphization.type_args.iter().rev());
+                        }
+                    }
                     RsTypeKind::Pointer { pointee, .. } => self.todo.push(pointee),
                     RsTypeKind::Reference { referent, .. } => self.todo.push(referent),
                     RsTypeKind::RvalueReference { referent, .. } => self.todo.push(referent),
@@ -1073,6 +1639,9 @@ impl<'ty> Iterator for RsTypeKindIter<'ty> {
                     RsTypeKind::Option(t) => self.todo.push(t),
                     RsTypeKind::BridgeType { .. } => {}
                     RsTypeKind::Other { type_args, .. } => self.todo.extend(type_args.iter().rev()),
+                    // Placeholders only occur in patterns, never in a real lowered type,
+                    // and have no children to visit.
+                    RsTypeKind::Placeholder(_) => {}
                 };
                 Some(curr)
             }
@@ -1085,6 +1654,7 @@ mod tests {
     use super::*;
     use arc_anyhow::Result;
     use googletest::prelude::*;
+    use ir_testing::ir_from_cc;
     use token_stream_matchers::assert_rs_matches;
 
     #[gtest]
@@ -1142,6 +1712,7 @@ mod tests {
                 abi: "blah".into(),
                 param_types: Rc::from([a, b]),
                 return_type: Rc::new(c),
+                bound_lifetimes: Rc::from([]),
             }
         };
         let dfs_names = f
@@ -1155,6 +1726,58 @@ mod tests {
         assert_eq!(vec!["fn", "A", "B", "C"], dfs_names);
     }
 
+    #[gtest]
+    fn test_by_value_strategy_direct_for_record_with_only_verified_fields() -> Result<()> {
+        // Every field is a primitive scalar, so each one is individually a verified
+        // faithful copy -- combined with the struct being trivially
+        // copyable/destructible and `Unpin`, this record is eligible for `Direct`
+        // by-value passing.
+        let ir = ir_from_cc(
+            r#"
+            struct SomeStruct final {
+                int a;
+                float b;
+            };
+            "#,
+        )?;
+        let record = ir.records().find(|r| &*r.identifier.identifier == "SomeStruct").unwrap();
+        assert!(check_by_value(record).is_ok());
+        assert!(record.fields.iter().all(field_is_verified_faithful_copy));
+        Ok(())
+    }
+
+    #[gtest]
+    fn test_by_value_strategy_rejects_nested_unverified_field() -> Result<()> {
+        // `inner`'s type is itself a record, so `field_is_verified_faithful_copy` has no
+        // way to confirm (from the field's `rs_type` alone) that `Inner` wasn't reshaped
+        // into something other than a byte-for-byte copy of its C++ layout -- e.g. for an
+        // unsupported member, or a `no_unique_address` field. Such fields must be
+        // conservatively treated as unverified, so `Outer` isn't eligible for `Direct`
+        // by-value passing even though it's otherwise trivial.
+        let ir = ir_from_cc(
+            r#"
+            struct Inner final {
+                int x;
+            };
+            struct Outer final {
+                int a;
+                Inner inner;
+            };
+            "#,
+        )?;
+        let outer = ir.records().find(|r| &*r.identifier.identifier == "Outer").unwrap();
+        assert!(check_by_value(outer).is_ok());
+        assert!(!outer.fields.iter().all(field_is_verified_faithful_copy));
+        let unverified_fields: Vec<_> = outer
+            .fields
+            .iter()
+            .filter(|f| !field_is_verified_faithful_copy(f))
+            .map(|f| &*f.identifier.identifier)
+            .collect();
+        assert_eq!(unverified_fields, vec!["inner"]);
+        Ok(())
+    }
+
     #[gtest]
     fn test_lifetime_elision_for_references() {
         let type_args: &[RsTypeKind] = &[];
@@ -1201,8 +1824,9 @@ mod tests {
             lifetime: Lifetime::new("a"),
         }
         .format_as_self_param()?;
-        assert_rs_matches!(result.tokens, quote! {self: ::ctor::RvalueReference<'a, Self>});
+        assert_rs_matches!(result.tokens, quote! {self: RvalueReference<'a, Self>});
         assert_eq!(result.features, [make_rs_ident("arbitrary_self_types")].into_iter().collect());
+        assert_eq!(result.imports, [UsePath::new("ctor::RvalueReference")].into_iter().collect());
         Ok(())
     }
 
@@ -1220,8 +1844,9 @@ mod tests {
             lifetime: Lifetime::new("a"),
         }
         .format_as_self_param()?;
-        assert_rs_matches!(result.tokens, quote! {self: ::ctor::ConstRvalueReference<'a, Self>});
+        assert_rs_matches!(result.tokens, quote! {self: ConstRvalueReference<'a, Self>});
         assert_eq!(result.features, [make_rs_ident("arbitrary_self_types")].into_iter().collect());
+        assert_eq!(result.imports, [UsePath::new("ctor::ConstRvalueReference")].into_iter().collect());
         Ok(())
     }
 
@@ -1243,11 +1868,13 @@ mod tests {
                 abi: "C".into(),
                 return_type: Rc::new(reference.clone()),
                 param_types: no_types.into(),
+                bound_lifetimes: Rc::from([]),
             },
             RsTypeKind::FuncPtr {
                 abi: "C".into(),
                 return_type: Rc::new(int),
                 param_types: Rc::from([reference]),
+                bound_lifetimes: Rc::from([]),
             },
         ] {
             let (missing_features, reason) = func_ptr.required_crubit_features(
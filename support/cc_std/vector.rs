@@ -2,9 +2,46 @@
 // Exceptions. See /LICENSE for license information.
 // SPDX-License-Identifier: Apache-2.0 WITH LLVM-exception
 
+use std::alloc::Layout;
 use std::ops::{Deref, DerefMut};
 use std::ops::{Index, IndexMut};
 
+/// Routes `Vector<T>`'s storage allocation through C++'s global allocator
+/// (the scalar `::operator new` / `::operator delete`, the same overloads
+/// `std::allocator<T>` calls), so growing or freeing the storage from the
+/// Rust side produces exactly the blocks a real `std::vector`'s destructor
+/// (running on the C++ side) can legally free. Mixing this with Rust's global
+/// allocator would be undefined behavior, since the two are not guaranteed to
+/// be the same allocator.
+mod allocator {
+    use std::alloc::Layout;
+
+    extern "C" {
+        fn __rust_thunk___cc_std_Vector_New(size: usize, align: usize) -> *mut u8;
+        fn __rust_thunk___cc_std_Vector_Delete(ptr: *mut u8, align: usize);
+    }
+
+    /// Allocates a block of `layout` via
+    /// `::operator new(size, align_val_t, nothrow)`. The `nothrow` overload
+    /// is used deliberately: the plain overload throws `std::bad_alloc` on
+    /// failure instead of returning null, which would unwind a C++ exception
+    /// straight through the `extern "C"` thunk (undefined behavior) instead
+    /// of hitting the assert below.
+    pub(crate) unsafe fn allocate(layout: Layout) -> *mut u8 {
+        let ptr = __rust_thunk___cc_std_Vector_New(layout.size(), layout.align());
+        assert!(!ptr.is_null(), "`::operator new` returned null for {:?}", layout);
+        ptr
+    }
+
+    /// Frees a block previously returned by `allocate`, via
+    /// `::operator delete(ptr, align_val_t)`.
+    pub(crate) unsafe fn deallocate(ptr: *mut u8, layout: Layout) {
+        if !ptr.is_null() {
+            __rust_thunk___cc_std_Vector_Delete(ptr, layout.align());
+        }
+    }
+}
+
 /// A mutable, contiguous, dynamically-sized container of elements of type `T`,
 /// ABI-compatible with `std::vector` from C++.
 /// This layout was found empirically on Linux with modern g++ and libc++. If
@@ -20,16 +57,20 @@ pub struct Vector<T> {
 // TODO(b/356221873): Add a test that checks that the layout of this struct is
 // consistent with the layout of `std::vector` from C++.
 // TODO(b/356221873): Implement Send and Sync.
-// TODO(b/356221873): Implement conversion to and from std::Vec
-// TODO(b/356221873): Implement FromIterator, FromIteratorMut.
-// TODO(b/356221873): Implement function for resizing (resize, shrink_to_fit,
-// reserve etc).
-// TODO(b/356221873): Implement clear().
 // TODO(b/356221873): implement insertion, removal of elements.
 // TODO(b/356221873): implement append, extend.
 
 impl<T> Vector<T> {
     pub fn new() -> Vector<T> {
+        // `Layout` (and so the `::operator new`/`delete` thunks in
+        // `allocator`, which size and align every allocation off of
+        // `align_of::<T>()`) requires an alignment that fits in an `isize`.
+        // Every `T` Rust can construct satisfies this today, but assert it --
+        // like the `align_of`/`size_of` checks `rs_bindings_from_cc` emits
+        // for over-aligned records such as `HasCustomAlignment` -- so a
+        // future `T` that somehow violated it would fail loudly here rather
+        // than through unsound indexing into a misaligned `Vector`.
+        const { assert!(core::mem::align_of::<T>() <= (isize::MAX as usize)) };
         Vector {
             begin: core::ptr::null_mut(),
             end: core::ptr::null_mut(),
@@ -60,33 +101,197 @@ impl<T> Vector<T> {
             unsafe { self.capacity_end.offset_from(self.begin).try_into().unwrap() }
         }
     }
+
+    /// Drops the trailing elements down to `len`, without touching capacity.
+    ///
+    /// Lowers `end` before running any destructor, so a panic partway through
+    /// a `T::drop` still leaves `self` in a valid, double-drop-free state
+    /// (the same ordering `Vec::truncate` uses).
+    pub fn truncate(&mut self, len: usize) {
+        let old_len = self.len();
+        if len >= old_len {
+            return;
+        }
+        unsafe {
+            let new_end = self.begin.add(len);
+            let tail = core::ptr::slice_from_raw_parts_mut(new_end, old_len - len);
+            self.end = new_end;
+            core::ptr::drop_in_place(tail);
+        }
+    }
+
+    /// Drops every element, without freeing the backing storage.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
 }
 
 impl<T: Unpin> Vector<T> {
-    /// Mutates `self` as if it were a `Vec<T>`.
-    fn mutate_self_as_vec<F, R>(&mut self, mutate_self: F) -> R
+    /// Reallocates the backing storage to hold exactly `new_capacity`
+    /// elements (`new_capacity >= self.len()`), through C++'s allocator.
+    ///
+    /// Allocates the new block through C++'s allocator, relocates the
+    /// existing elements into it with a byte-for-byte copy, and frees the old
+    /// block through C++'s allocator -- never through Rust's global
+    /// allocator, which isn't guaranteed to be the same one and would be
+    /// undefined behavior to mix with C++'s `operator new`/`delete`. A
+    /// `new_capacity` of zero frees the storage and leaves `self` in the same
+    /// all-null state as `Vector::new()`.
+    fn realloc_to(&mut self, new_capacity: usize) {
+        debug_assert!(new_capacity >= self.len());
+        let len = self.len();
+        let new_begin = if new_capacity == 0 {
+            core::ptr::null_mut()
+        } else {
+            let new_layout =
+                Layout::array::<T>(new_capacity).expect("`Vector<T>` capacity overflow");
+            let new_begin = unsafe { allocator::allocate(new_layout) } as *mut T;
+            if len > 0 {
+                unsafe { core::ptr::copy_nonoverlapping(self.begin, new_begin, len) };
+            }
+            new_begin
+        };
+        if !self.begin.is_null() {
+            // `Layout::array` can't fail here: it already succeeded for this
+            // same `T` and a capacity (`self.capacity()`) that was allocated
+            // successfully before.
+            let old_layout = Layout::array::<T>(self.capacity()).unwrap();
+            unsafe { allocator::deallocate(self.begin as *mut u8, old_layout) };
+        }
+        self.begin = new_begin;
+        self.end = if new_begin.is_null() { new_begin } else { unsafe { new_begin.add(len) } };
+        self.capacity_end =
+            if new_begin.is_null() { new_begin } else { unsafe { new_begin.add(new_capacity) } };
+    }
+
+    /// Grows the backing storage to make room for at least one more element,
+    /// geometrically doubling (to match libc++/libstdc++ growth behavior, so
+    /// the resulting layout stays interoperable) rather than growing to
+    /// exactly what's needed.
+    fn grow(&mut self) {
+        let old_capacity = self.capacity();
+        let new_capacity = if old_capacity == 0 { 1 } else { old_capacity * 2 };
+        self.realloc_to(new_capacity);
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.len() == self.capacity() {
+            self.grow();
+        }
+        unsafe {
+            self.end.write(value);
+            self.end = self.end.add(1);
+        }
+    }
+
+    /// Reserves capacity for at least `additional` more elements, through
+    /// C++'s allocator. Does nothing if capacity is already sufficient.
+    pub fn reserve(&mut self, additional: usize) {
+        let required =
+            self.len().checked_add(additional).expect("`Vector<T>` capacity overflow");
+        if required > self.capacity() {
+            self.realloc_to(required);
+        }
+    }
+
+    /// Reallocates the backing storage down to exactly `self.len()`, through
+    /// C++'s allocator.
+    pub fn shrink_to_fit(&mut self) {
+        let len = self.len();
+        if len < self.capacity() {
+            self.realloc_to(len);
+        }
+    }
+
+    /// Resizes to `new_len`, either truncating (dropping the excess
+    /// elements, as in `Vector::truncate`) or extending (reserving capacity,
+    /// then calling `f()` once per new trailing element and writing it in
+    /// place).
+    pub fn resize_with<F>(&mut self, new_len: usize, mut f: F)
     where
-        F: FnOnce(&mut Vec<T>) -> R,
+        F: FnMut() -> T,
     {
+        let len = self.len();
+        if new_len <= len {
+            self.truncate(new_len);
+            return;
+        }
+        self.reserve(new_len - len);
+        for i in len..new_len {
+            unsafe { self.begin.add(i).write(f()) };
+            self.end = unsafe { self.begin.add(i + 1) };
+        }
+    }
+}
+
+impl<T: Unpin> Vector<T> {
+    /// Moves every element out into a freshly allocated `Vec<T>`, then frees
+    /// the (now-empty) C++-allocated block.
+    ///
+    /// This can't just hand `self`'s storage straight to `Vec` the way
+    /// `Vec::from_raw_parts` does -- the block was allocated by C++'s
+    /// `operator new`, not Rust's global allocator, so a `Vec` built
+    /// directly from it would later `dealloc` through the wrong allocator.
+    /// Moving element-by-element instead keeps each allocator's blocks on its
+    /// own side.
+    pub fn into_vec(mut self) -> Vec<T> {
+        let len = self.len();
+        let mut result = Vec::with_capacity(len);
         unsafe {
-            let mut v = if self.begin.is_null() {
-                Vec::new()
-            } else {
-                Vec::from_raw_parts(self.begin, self.len(), self.capacity())
-            };
-            let result = mutate_self(&mut v);
-            let len = v.len();
-            let capacity = v.capacity();
-            self.begin = v.as_mut_ptr();
-            self.end = self.begin.add(len);
-            self.capacity_end = self.begin.add(capacity);
-            core::mem::forget(v);
-            result
+            core::ptr::copy_nonoverlapping(self.begin, result.as_mut_ptr(), len);
+            result.set_len(len);
+            // The elements now belong to `result`; `self` must no longer drop
+            // them when it frees its (now logically empty) storage below.
+            self.end = self.begin;
         }
+        result
     }
 
-    pub fn push(&mut self, value: T) {
-        self.mutate_self_as_vec(|v| v.push(value));
+    /// Moves every element of `vec` into a freshly allocated, C++-owned
+    /// block, then lets `vec` drop its own (now-empty) allocation normally.
+    pub fn from_vec(mut vec: Vec<T>) -> Vector<T> {
+        let len = vec.len();
+        let mut result = Vector::new();
+        if len > 0 {
+            result.reserve(len);
+            unsafe {
+                core::ptr::copy_nonoverlapping(vec.as_ptr(), result.begin, len);
+                result.end = result.begin.add(len);
+                vec.set_len(0);
+            }
+        }
+        result
+    }
+}
+
+impl<T: Unpin> From<Vec<T>> for Vector<T> {
+    fn from(vec: Vec<T>) -> Self {
+        Vector::from_vec(vec)
+    }
+}
+
+impl<T: Unpin> From<Vector<T>> for Vec<T> {
+    fn from(vector: Vector<T>) -> Self {
+        vector.into_vec()
+    }
+}
+
+impl<T: Unpin> FromIterator<T> for Vector<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut result = Vector::new();
+        result.extend(iter);
+        result
+    }
+}
+
+impl<T: Unpin> Extend<T> for Vector<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower_bound, _) = iter.size_hint();
+        self.reserve(lower_bound);
+        for value in iter {
+            self.push(value);
+        }
     }
 }
 
@@ -98,8 +303,13 @@ impl<T> Default for Vector<T> {
 
 impl<T> Drop for Vector<T> {
     fn drop(&mut self) {
+        if self.begin.is_null() {
+            return;
+        }
         unsafe {
-            _ = Vec::from_raw_parts(self.begin, self.len(), self.capacity());
+            core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(self.begin, self.len()));
+            let layout = Layout::array::<T>(self.capacity()).unwrap();
+            allocator::deallocate(self.begin as *mut u8, layout);
         }
     }
 }
@@ -136,4 +346,207 @@ impl<T: Unpin> DerefMut for Vector<T> {
             unsafe { std::slice::from_raw_parts_mut(self.begin, self.len()) }
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_new_is_empty() {
+        let v = Vector::<i32>::new();
+        assert_eq!(v.len(), 0);
+        assert_eq!(v.capacity(), 0);
+        assert!(v.is_empty());
+        assert_eq!(&*v, &[] as &[i32]);
+    }
+
+    #[test]
+    fn test_push_and_index() {
+        let mut v = Vector::<i32>::new();
+        for i in 0..5 {
+            v.push(i);
+        }
+        assert_eq!(v.len(), 5);
+        for i in 0..5 {
+            assert_eq!(v[i as usize], i);
+        }
+    }
+
+    #[test]
+    fn test_push_grows_capacity_by_doubling() {
+        // Matches libc++/libstdc++ growth behavior: 0 -> 1 -> 2 -> 4 -> 8 ...
+        let mut v = Vector::<i32>::new();
+        let mut expected_capacity = 0;
+        for i in 0..9 {
+            v.push(i);
+            if v.len() > expected_capacity {
+                expected_capacity = if expected_capacity == 0 { 1 } else { expected_capacity * 2 };
+            }
+            assert_eq!(v.capacity(), expected_capacity, "after pushing element {i}");
+        }
+    }
+
+    #[test]
+    fn test_truncate_drops_trailing_elements() {
+        let counter = Rc::new(Cell::new(0));
+        struct DropCounter(Rc<Cell<i32>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        let mut v = Vector::<DropCounter>::new();
+        for _ in 0..5 {
+            v.push(DropCounter(counter.clone()));
+        }
+        let capacity_before = v.capacity();
+        v.truncate(2);
+        assert_eq!(v.len(), 2);
+        assert_eq!(counter.get(), 3);
+        // Truncating doesn't touch capacity.
+        assert_eq!(v.capacity(), capacity_before);
+    }
+
+    #[test]
+    fn test_truncate_to_longer_length_is_a_no_op() {
+        let mut v = Vector::<i32>::new();
+        v.push(1);
+        v.truncate(10);
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    fn test_clear_drops_all_elements_but_keeps_capacity() {
+        let counter = Rc::new(Cell::new(0));
+        struct DropCounter(Rc<Cell<i32>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        let mut v = Vector::<DropCounter>::new();
+        for _ in 0..3 {
+            v.push(DropCounter(counter.clone()));
+        }
+        let capacity_before = v.capacity();
+        v.clear();
+        assert_eq!(v.len(), 0);
+        assert!(v.is_empty());
+        assert_eq!(counter.get(), 3);
+        assert_eq!(v.capacity(), capacity_before);
+    }
+
+    #[test]
+    fn test_resize_with_extends_and_truncates() {
+        let mut v = Vector::<i32>::new();
+        let mut next = 0;
+        v.resize_with(3, || {
+            next += 1;
+            next
+        });
+        assert_eq!(&*v, &[1, 2, 3]);
+
+        v.resize_with(1, || unreachable!("shrinking shouldn't call the closure"));
+        assert_eq!(&*v, &[1]);
+    }
+
+    #[test]
+    fn test_reserve_grows_capacity_at_least_as_requested() {
+        let mut v = Vector::<i32>::new();
+        v.push(1);
+        v.reserve(10);
+        assert!(v.capacity() >= 11);
+        assert_eq!(&*v, &[1]);
+    }
+
+    #[test]
+    fn test_reserve_is_a_no_op_when_capacity_is_already_sufficient() {
+        let mut v = Vector::<i32>::new();
+        v.reserve(4);
+        let capacity_after_first_reserve = v.capacity();
+        v.reserve(1);
+        assert_eq!(v.capacity(), capacity_after_first_reserve);
+    }
+
+    #[test]
+    fn test_shrink_to_fit_reallocates_down_to_len() {
+        let mut v = Vector::<i32>::new();
+        v.reserve(10);
+        v.push(1);
+        v.push(2);
+        v.shrink_to_fit();
+        assert_eq!(v.capacity(), v.len());
+        assert_eq!(&*v, &[1, 2]);
+    }
+
+    #[test]
+    fn test_into_vec_from_vec_round_trip() {
+        let original = vec![1, 2, 3, 4];
+        let vector = Vector::from_vec(original.clone());
+        assert_eq!(&*vector, &original[..]);
+        let round_tripped = vector.into_vec();
+        assert_eq!(round_tripped, original);
+    }
+
+    #[test]
+    fn test_from_vec_of_empty_vec() {
+        let vector = Vector::<i32>::from_vec(Vec::new());
+        assert!(vector.is_empty());
+        assert_eq!(vector.into_vec(), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_from_into_trait_impls_round_trip() {
+        let original = vec![5, 6, 7];
+        let vector: Vector<i32> = original.clone().into();
+        let back: Vec<i32> = vector.into();
+        assert_eq!(back, original);
+    }
+
+    #[test]
+    fn test_from_iterator_and_extend() {
+        let mut vector: Vector<i32> = (0..4).collect();
+        assert_eq!(&*vector, &[0, 1, 2, 3]);
+        vector.extend([4, 5]);
+        assert_eq!(&*vector, &[0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_drop_runs_exactly_once_per_element() {
+        let counter = Rc::new(Cell::new(0));
+        struct DropCounter(Rc<Cell<i32>>);
+        impl Drop for DropCounter {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        {
+            let mut v = Vector::<DropCounter>::new();
+            for _ in 0..4 {
+                v.push(DropCounter(counter.clone()));
+            }
+        }
+        assert_eq!(counter.get(), 4);
+    }
+
+    #[test]
+    fn test_over_aligned_element_type() {
+        #[repr(align(64))]
+        #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+        struct OverAligned(u64);
+
+        let mut v = Vector::<OverAligned>::new();
+        for i in 0..4u64 {
+            v.push(OverAligned(i));
+            // Every element must land on a 64-byte boundary, even as the backing
+            // storage gets reallocated by `grow`.
+            for element in v.iter() {
+                assert_eq!((element as *const OverAligned as usize) % 64, 0);
+            }
+        }
+        assert_eq!(&*v, &[OverAligned(0), OverAligned(1), OverAligned(2), OverAligned(3)]);
+    }
 }
\ No newline at end of file